@@ -0,0 +1,406 @@
+//! Agenda view, like org-agenda: the todos of a span of days, grouped by day.
+//! On every day of the span, a todo shows up if it is scheduled on that day, if its deadline
+//! is on that day, or if a timestamp of its title is on that day. Today also lists the
+//! scheduled todos that are not done yet ("Sched. 5x:"), the deadlines that are past
+//! ("2 d. ago:"), and the deadlines that are close enough to warn about ("In 3 d.:").
+//! Timed entries come first, sorted by time, with a marker at the current time.
+use crate::parsing::timestamp::{TimeUnit, Timestamp};
+use crate::parsing::Todo;
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// Days before a deadline at which it shows up today, when the deadline has no warning delay.
+pub const DEADLINE_WARNING_DAYS: i64 = 14;
+/// Priority of the todos without priority cookie, as in org.
+const DEFAULT_PRIORITY: char = 'B';
+/// Line showing the current time among the timed entries of today.
+const NOW_MARKER: &str = "now - - - - - - - - - - - - - - - - - - - - - - - - -";
+
+/// The days shown by the agenda.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Span {
+    /// Today only
+    Day,
+    /// The current week, from Monday
+    Week,
+    /// This many days, from today
+    Days(u32),
+}
+
+/// Error returned when a span is neither "day", "week" nor a number of days.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InvalidSpan(pub String);
+
+/// Why a todo shows up on a day.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EntryKind {
+    Scheduled,
+    /// Scheduled this many days ago, and still not done
+    ScheduledOverdue(i64),
+    Deadline,
+    /// The deadline is in this many days
+    DeadlineWarning(i64),
+    /// The deadline was this many days ago
+    DeadlineOverdue(i64),
+    /// A timestamp of the title. For a date range, the day of the range and its number of days
+    Timestamp(Option<(i64, i64)>),
+}
+
+/// A todo shown on a day of the agenda.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Entry {
+    pub todo: Todo,
+    pub kind: EntryKind,
+    /// Time of the entry, for timed entries
+    pub time: Option<NaiveTime>,
+    /// End of the time range of the entry
+    pub end_time: Option<NaiveTime>,
+}
+
+/// The entries of a single day.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Day {
+    pub date: NaiveDate,
+    /// Timed entries sorted by time, then the other entries sorted by priority
+    pub entries: Vec<Entry>,
+}
+
+/// The agenda of a span of days.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Agenda {
+    pub days: Vec<Day>,
+    /// Moment the agenda was built at, used for today and the current time marker
+    pub now: NaiveDateTime,
+}
+
+impl Span {
+    /// First day and number of days of the span, around `today`.
+    pub fn range(&self, today: NaiveDate) -> (NaiveDate, u32) {
+        match self {
+            Span::Day => (today, 1),
+            Span::Week => (today.week(Weekday::Mon).first_day(), 7),
+            Span::Days(days) => (today, *days),
+        }
+    }
+    /// Number of days of the span.
+    pub fn days(&self) -> u32 {
+        match self {
+            Span::Day => 1,
+            Span::Week => 7,
+            Span::Days(days) => *days,
+        }
+    }
+}
+
+impl Agenda {
+    /// Build the agenda of `days` days from `start`, at `now`.
+    pub fn new(todos: &[Todo], start: NaiveDate, days: u32, now: NaiveDateTime) -> Agenda {
+        let days = start
+            .iter_days()
+            .take(days as usize)
+            .map(|date| {
+                let mut entries: Vec<Entry> = todos
+                    .iter()
+                    .flat_map(|todo| entries_on(todo, date, now.date()))
+                    .collect();
+                entries.sort_by_key(|e| {
+                    let priority = e.todo.priority().unwrap_or(DEFAULT_PRIORITY);
+                    (
+                        e.time.is_none(),
+                        e.time,
+                        priority,
+                        e.todo.title().to_string(),
+                    )
+                });
+                Day { date, entries }
+            })
+            .collect();
+        Agenda { days, now }
+    }
+}
+
+/// The entries of a todo on `date`.
+fn entries_on(todo: &Todo, date: NaiveDate, today: NaiveDate) -> Vec<Entry> {
+    let mut entries = vec![];
+    let mut entry = |kind, timestamp: Option<&Timestamp>| {
+        let timestamp = timestamp.filter(|t| t.end.is_none_or(|end| end.date == t.start.date));
+        entries.push(Entry {
+            todo: todo.clone(),
+            kind,
+            time: timestamp.and_then(|t| t.start.time),
+            end_time: timestamp.and_then(|t| t.end?.time),
+        });
+    };
+    if let Some(scheduled) = todo.scheduled() {
+        let base = scheduled.start.date;
+        if date == today && base < today {
+            entry(EntryKind::ScheduledOverdue((today - base).num_days()), None);
+        } else if occurs_on(scheduled, date) && (date >= today || date == base) {
+            entry(EntryKind::Scheduled, Some(scheduled));
+        }
+    }
+    if let Some(deadline) = todo.deadline() {
+        let base = deadline.start.date;
+        let days_left = (base - today).num_days();
+        if date == today && base < today {
+            entry(EntryKind::DeadlineOverdue(-days_left), None);
+        } else if occurs_on(deadline, date) && (date >= today || date == base) {
+            entry(EntryKind::Deadline, Some(deadline));
+        } else if date == today && days_left <= warning_days(deadline) {
+            entry(EntryKind::DeadlineWarning(days_left), None);
+        }
+    }
+    if let Some(timestamp) = todo.timestamp() {
+        match timestamp.end.filter(|end| end.date > timestamp.start.date) {
+            Some(end) if (timestamp.start.date..=end.date).contains(&date) => {
+                let day = (date - timestamp.start.date).num_days() + 1;
+                let length = (end.date - timestamp.start.date).num_days() + 1;
+                let first = Some(timestamp).filter(|_| day == 1);
+                entry(EntryKind::Timestamp(Some((day, length))), first);
+            }
+            Some(_) => {}
+            None if occurs_on(timestamp, date) => {
+                entry(EntryKind::Timestamp(None), Some(timestamp))
+            }
+            None => {}
+        }
+    }
+    entries
+}
+
+/// Verify if an occurrence of a timestamp is on `date`.
+fn occurs_on(timestamp: &Timestamp, date: NaiveDate) -> bool {
+    let before = date.and_time(NaiveTime::MIN) - Duration::seconds(1);
+    timestamp
+        .next_occurrence(before)
+        .is_some_and(|occurrence| occurrence.date() == date)
+}
+
+/// Days before a deadline at which it shows up today: its warning delay, else the default.
+fn warning_days(deadline: &Timestamp) -> i64 {
+    let Some(delay) = deadline.delay else {
+        return DEADLINE_WARNING_DAYS;
+    };
+    let value = i64::from(delay.value);
+    match delay.unit {
+        TimeUnit::Hour => 0,
+        TimeUnit::Day => value,
+        TimeUnit::Week => value * 7,
+        TimeUnit::Month => value * 30,
+        TimeUnit::Year => value * 365,
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Span::Day => write!(f, "Day-agenda"),
+            Span::Week => write!(f, "Week-agenda"),
+            Span::Days(days) => write!(f, "{days}-day-agenda"),
+        }
+    }
+}
+
+impl FromStr for Span {
+    type Err = InvalidSpan;
+    /// Parse a span as "day", "week", or a number of days
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "day" => Ok(Span::Day),
+            "week" => Ok(Span::Week),
+            days => match days.parse() {
+                Ok(days) if days > 0 => Ok(Span::Days(days)),
+                _ => Err(InvalidSpan(String::from(s))),
+            },
+        }
+    }
+}
+
+impl fmt::Display for InvalidSpan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invalid agenda span: {} (expected day, week or a number of days)",
+            self.0
+        )
+    }
+}
+
+impl Error for InvalidSpan {}
+
+impl fmt::Display for Entry {
+    /// Write the entry as org does. Example: "  work:       10:00-11:30 Scheduled:  TODO Report"
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "  {:<12}", format!("{}:", self.todo.category()))?;
+        if let Some(time) = self.time {
+            let time = match self.end_time {
+                Some(end) => format!("{}-{}", time.format("%H:%M"), end.format("%H:%M")),
+                None => time.format("%H:%M").to_string(),
+            };
+            write!(f, "{time:<12}")?;
+        }
+        match self.kind {
+            EntryKind::Scheduled => write!(f, "Scheduled: ")?,
+            EntryKind::ScheduledOverdue(days) => write!(f, "Sched.{days:2}x: ")?,
+            EntryKind::Deadline => write!(f, "Deadline:  ")?,
+            EntryKind::DeadlineWarning(days) => write!(f, "In {days:3} d.: ")?,
+            EntryKind::DeadlineOverdue(days) => write!(f, "{days:2} d. ago: ")?,
+            EntryKind::Timestamp(Some((day, length))) => write!(f, "({day}/{length}): ")?,
+            EntryKind::Timestamp(None) => {}
+        }
+        write!(f, "{}", self.todo.keyword())?;
+        if let Some(priority) = self.todo.priority() {
+            write!(f, " [#{priority}]")?;
+        }
+        write!(f, " {}", self.todo.title())
+    }
+}
+
+impl fmt::Display for Agenda {
+    /// Write the agenda as org does, with a header line, then a line per day followed by its
+    /// entries. Mondays show their week number.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (Some(first), Some(last)) = (self.days.first(), self.days.last()) else {
+            return Ok(());
+        };
+        let span = match self.days.len() {
+            1 => Span::Day,
+            7 if first.date.weekday() == Weekday::Mon => Span::Week,
+            days => Span::Days(days as u32),
+        };
+        let (first_week, last_week) = (first.date.iso_week().week(), last.date.iso_week().week());
+        if first_week == last_week {
+            writeln!(f, "{span} (W{first_week:02}):")?;
+        } else {
+            writeln!(f, "{span} (W{first_week:02}-W{last_week:02}):")?;
+        }
+        for day in &self.days {
+            let date = day.date;
+            write!(
+                f,
+                "{:<10} {:>2} {} {}",
+                date.format("%A").to_string(),
+                date.day(),
+                date.format("%B"),
+                date.year()
+            )?;
+            if date.weekday() == Weekday::Mon {
+                write!(f, " W{:02}", date.iso_week().week())?;
+            }
+            writeln!(f)?;
+            // The marker goes after the entries timed before now
+            let marker = (date == self.now.date()).then(|| {
+                day.entries
+                    .iter()
+                    .take_while(|e| e.time.is_some_and(|t| t <= self.now.time()))
+                    .count()
+            });
+            for (i, entry) in day.entries.iter().enumerate() {
+                if marker == Some(i) {
+                    writeln!(
+                        f,
+                        "  {:<12}{}...... {NOW_MARKER}",
+                        "",
+                        self.now.format("%H:%M")
+                    )?;
+                }
+                writeln!(f, "{entry}")?;
+            }
+            if marker == Some(day.entries.len()) {
+                writeln!(
+                    f,
+                    "  {:<12}{}...... {NOW_MARKER}",
+                    "",
+                    self.now.format("%H:%M")
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Agenda, EntryKind, Span};
+    use crate::parsing::{parse_file, Todo};
+    use chrono::{NaiveDate, NaiveDateTime};
+    use std::path::Path;
+
+    const FILE: &str = "* TODO Standup\nSCHEDULED: <2023-09-06 Wed 09:00 +1d>\n* TODO [#A] Call the bank\nSCHEDULED: <2023-09-01 Fri>\n* TODO Taxes\nDEADLINE: <2023-09-09 Sat>\n* TODO Passport\nDEADLINE: <2023-09-30 Sat -30d>\n* TODO Report\nDEADLINE: <2023-09-04 Mon>\n* TODO Lunch <2023-09-06 Wed 12:00-13:00>\n* TODO Conference <2023-09-07 Thu>--<2023-09-08 Fri>\n";
+
+    fn todos() -> Vec<Todo> {
+        parse_file(Path::new("work.org"), FILE, &Default::default()).value
+    }
+    fn date(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2023, 9, d).unwrap()
+    }
+    fn at(d: u32, h: u32, m: u32) -> NaiveDateTime {
+        date(d).and_hms_opt(h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn entries_by_day() {
+        let agenda = Agenda::new(&todos(), date(6), 3, at(6, 10, 0));
+        let kinds = |day: usize| -> Vec<(&str, EntryKind)> {
+            agenda.days[day]
+                .entries
+                .iter()
+                .map(|e| (e.todo.title(), e.kind))
+                .collect()
+        };
+        assert_eq!(
+            kinds(0),
+            [
+                ("Standup", EntryKind::Scheduled),
+                (
+                    "Lunch <2023-09-06 Wed 12:00-13:00>",
+                    EntryKind::Timestamp(None)
+                ),
+                ("Call the bank", EntryKind::ScheduledOverdue(5)),
+                ("Passport", EntryKind::DeadlineWarning(24)),
+                ("Report", EntryKind::DeadlineOverdue(2)),
+                ("Taxes", EntryKind::DeadlineWarning(3)),
+            ]
+        );
+        assert_eq!(
+            kinds(1),
+            [
+                ("Standup", EntryKind::Scheduled),
+                (
+                    "Conference <2023-09-07 Thu>--<2023-09-08 Fri>",
+                    EntryKind::Timestamp(Some((1, 2)))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn agenda_display() {
+        let agenda = Agenda::new(&todos(), date(6), 1, at(6, 10, 0));
+        let expected = "Day-agenda (W36):
+Wednesday   6 September 2023
+  work:       09:00       Scheduled: TODO Standup
+              10:00...... now - - - - - - - - - - - - - - - - - - - - - - - - -
+  work:       12:00-13:00 TODO Lunch <2023-09-06 Wed 12:00-13:00>
+  work:       Sched. 5x: TODO [#A] Call the bank
+  work:       In  24 d.: TODO Passport
+  work:        2 d. ago: TODO Report
+  work:       In   3 d.: TODO Taxes
+";
+        assert_eq!(agenda.to_string(), expected);
+    }
+
+    #[test]
+    fn spans() {
+        assert_eq!("week".parse(), Ok(Span::Week));
+        assert_eq!("10".parse(), Ok(Span::Days(10)));
+        assert!("0".parse::<Span>().is_err());
+        assert_eq!(Span::Week.range(date(6)), (date(4), 7));
+        let agenda = Agenda::new(&[], date(4), 7, at(6, 10, 0));
+        assert!(agenda.to_string().starts_with(
+            "Week-agenda (W36):\nMonday      4 September 2023 W36\nTuesday     5 September 2023\n"
+        ));
+    }
+}
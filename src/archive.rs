@@ -0,0 +1,360 @@
+//! Refiling and archiving of subtrees, from one org file to another or within a file.
+//! A subtree is cut from its file and pasted as the last sub-heading of a target heading, or
+//! at the end of the target file, its headings shifted to their new level. Archiving also
+//! records where the subtree came from in its property drawer, like org-archive-subtree.
+//! The target heading is added when missing, as org does for "::* Archive".
+use crate::parsing::document::{Document, Heading};
+use crate::parsing::edit::{append_entry, set_property, Edit};
+use crate::parsing::is_heading;
+use crate::parsing::keywords::KeywordState;
+use crate::parsing::properties::CATEGORY;
+use crate::parsing::timestamp::Timestamp;
+use chrono::NaiveDateTime;
+use std::convert::Infallible;
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Property holding when a subtree was archived. Example: "2023-09-06 Wed 18:30"
+pub const ARCHIVE_TIME_PROPERTY: &str = "ARCHIVE_TIME";
+/// Property holding the file a subtree was archived from.
+pub const ARCHIVE_FILE_PROPERTY: &str = "ARCHIVE_FILE";
+/// Property holding the outline path of the parents of an archived subtree. Example: "Work/Ops"
+pub const ARCHIVE_OLPATH_PROPERTY: &str = "ARCHIVE_OLPATH";
+/// Property holding the category of an archived subtree.
+pub const ARCHIVE_CATEGORY_PROPERTY: &str = "ARCHIVE_CATEGORY";
+/// Property holding the keyword of an archived subtree.
+pub const ARCHIVE_TODO_PROPERTY: &str = "ARCHIVE_TODO";
+
+/// Where the archived subtrees go, written like org-archive-location: "FILE::HEADING".
+/// "%s" in FILE is the path of the archived file, and an empty FILE is the archived file
+/// itself. Without HEADING the subtrees go at the end of the file. Example: "%s_archive::"
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ArchiveLocation {
+    pub file: String,
+    /// Outline path of the target heading
+    pub heading: Option<String>,
+}
+
+/// Edits moving subtrees: the ones of the file they are cut from and the ones of the target.
+/// Both apply to the same document when the subtrees move within a file.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Moved {
+    pub source: Vec<Edit>,
+    pub target: Vec<Edit>,
+}
+
+/// Error returned when subtrees cannot be moved.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ArchiveError {
+    /// The target heading is the subtree with this title, or one of its sub-headings
+    IntoItself(String),
+}
+
+impl ArchiveLocation {
+    /// Path of the archive of the file `source`.
+    pub fn file_for(&self, source: &Path) -> PathBuf {
+        if self.file.is_empty() {
+            return source.to_path_buf();
+        }
+        if self.file.contains("%s") {
+            return PathBuf::from(self.file.replace("%s", &source.to_string_lossy()));
+        }
+        let file = PathBuf::from(&self.file);
+        match source.parent() {
+            Some(dir) if file.is_relative() => dir.join(file),
+            _ => file,
+        }
+    }
+}
+
+impl Default for ArchiveLocation {
+    fn default() -> Self {
+        ArchiveLocation {
+            file: String::from("%s_archive"),
+            heading: None,
+        }
+    }
+}
+
+/// Move the subtrees of `headings` from `source` under the heading with the outline path
+/// `parent` in `target`, or at the end of `target`. Sub-headings of other given headings
+/// move with them.
+pub fn refile(
+    source: &Document,
+    headings: &[&Heading],
+    target: &Document,
+    parent: Option<&str>,
+) -> Result<Moved, ArchiveError> {
+    move_subtrees(source, headings, target, parent, |_| vec![])
+}
+
+/// Move the subtrees of `headings` like `refile`, adding the archive properties to each of
+/// them: when it was archived, from which file, under which parents and with which category.
+pub fn archive(
+    source: &Document,
+    headings: &[&Heading],
+    target: &Document,
+    parent: Option<&str>,
+    now: NaiveDateTime,
+) -> Result<Moved, ArchiveError> {
+    let time = Timestamp::inactive(now).to_string();
+    let time = time.trim_matches(['[', ']']);
+    let stem = source
+        .path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut origins: Vec<(usize, String, String)> = vec![];
+    source.visit(|heading, inherited| {
+        let category = match heading.property(CATEGORY) {
+            Some(category) => category,
+            None => inherited.properties.get(CATEGORY).unwrap_or(&stem),
+        };
+        let outline = inherited.outline.join("/");
+        origins.push((heading.span.start, outline, String::from(category)));
+    });
+    move_subtrees(source, headings, target, parent, |heading| {
+        let mut properties = vec![
+            (ARCHIVE_TIME_PROPERTY, String::from(time)),
+            (ARCHIVE_FILE_PROPERTY, source.path.display().to_string()),
+        ];
+        let origin = origins
+            .iter()
+            .find(|(start, ..)| *start == heading.span.start);
+        if let Some((_, outline, category)) = origin {
+            if !outline.is_empty() {
+                properties.push((ARCHIVE_OLPATH_PROPERTY, outline.clone()));
+            }
+            properties.push((ARCHIVE_CATEGORY_PROPERTY, category.clone()));
+        }
+        if let Some(keyword) = &heading.keyword {
+            properties.push((ARCHIVE_TODO_PROPERTY, keyword.clone()));
+        }
+        properties
+    })
+}
+
+/// The headings of `doc` marked done and closed before `before`, without the sub-headings of
+/// the ones returned. Example: the headings done more than 30 days ago.
+pub fn done_before(doc: &Document, before: NaiveDateTime) -> Vec<&Heading> {
+    let mut found: Vec<&Heading> = vec![];
+    for heading in doc.iter() {
+        let done = heading
+            .keyword
+            .as_deref()
+            .and_then(|k| doc.keywords.state(k))
+            == Some(KeywordState::Done);
+        let closed = heading.planning.closed.as_ref().map(Timestamp::datetime);
+        let inside = found.iter().any(|f| contains(f, heading));
+        if done && closed.is_some_and(|c| c < before) && !inside {
+            found.push(heading);
+        }
+    }
+    found
+}
+
+fn move_subtrees<F>(
+    source: &Document,
+    headings: &[&Heading],
+    target: &Document,
+    parent: Option<&str>,
+    mut properties: F,
+) -> Result<Moved, ArchiveError>
+where
+    F: FnMut(&Heading) -> Vec<(&'static str, String)>,
+{
+    let headings: Vec<&Heading> = headings
+        .iter()
+        .filter(|h| !headings.iter().any(|o| o.span != h.span && contains(o, h)))
+        .copied()
+        .collect();
+    if headings.is_empty() {
+        return Ok(Moved::default());
+    }
+    let (parent, missing) = match parent {
+        Some(path) => target_heading(target, path),
+        None => (None, vec![]),
+    };
+    if let Some(parent) = parent.filter(|_| source.path == target.path) {
+        if let Some(heading) = headings.iter().find(|h| contains(h, parent)) {
+            return Err(ArchiveError::IntoItself(heading.title.clone()));
+        }
+    }
+    let newline = target.newline();
+    let mut level = parent.map_or(1, |p| p.level + 1);
+    let mut entry = String::new();
+    for title in missing {
+        entry.push_str(&format!("{} {title}{newline}", "*".repeat(level)));
+        level += 1;
+    }
+    let mut moved = Moved::default();
+    for heading in headings {
+        let text = String::from(source.text(&heading.span));
+        let mut subtree = Document::parse(&source.path, text, &source.keywords).value;
+        for (name, value) in properties(heading) {
+            let edit = set_property(&subtree, &subtree.headings[0], name, &value);
+            subtree = subtree.edit(&[edit]).value;
+        }
+        // Every line keeps its own line ending
+        for line in subtree.source.split_inclusive('\n') {
+            let stars = line.len() - line.trim_start_matches('*').len();
+            if is_heading(line) {
+                let shifted = (stars + level).saturating_sub(heading.level).max(1);
+                entry.push_str(&"*".repeat(shifted));
+                entry.push_str(&line[stars..]);
+            } else {
+                entry.push_str(line);
+            }
+        }
+        if !entry.ends_with('\n') {
+            entry.push_str(newline);
+        }
+        moved.source.push(Edit::replace(heading.span.clone(), ""));
+    }
+    let entry = entry.strip_suffix('\n').unwrap_or(&entry);
+    let entry = entry.strip_suffix('\r').unwrap_or(entry);
+    moved.target.push(append_entry(target, parent, entry));
+    Ok(moved)
+}
+
+/// The deepest existing heading of an outline path, and the titles of the missing headings
+/// under it.
+fn target_heading<'a>(doc: &'a Document, path: &'a str) -> (Option<&'a Heading>, Vec<&'a str>) {
+    let titles: Vec<&str> = path.split(" / ").map(str::trim).collect();
+    for end in (1..=titles.len()).rev() {
+        if let Some(heading) = doc.find(&titles[..end].join(" / ")) {
+            return (Some(heading), titles[end..].to_vec());
+        }
+    }
+    (None, titles)
+}
+
+/// Whether `inner` is `outer` or one of its sub-headings.
+fn contains(outer: &Heading, inner: &Heading) -> bool {
+    outer.span.start <= inner.span.start && inner.span.end <= outer.span.end
+}
+
+impl FromStr for ArchiveLocation {
+    type Err = Infallible;
+    /// Parse "FILE::HEADING", the heading being written with or without its stars.
+    /// Example: "::* Archive"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (file, heading) = s.split_once("::").unwrap_or((s, ""));
+        let heading = heading.trim_start_matches('*').trim();
+        Ok(ArchiveLocation {
+            file: String::from(file),
+            heading: (!heading.is_empty()).then(|| String::from(heading)),
+        })
+    }
+}
+
+impl fmt::Display for ArchiveLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}::", self.file)?;
+        match &self.heading {
+            Some(heading) => write!(f, "* {heading}"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArchiveError::IntoItself(title) => {
+                write!(f, "cannot move {title} under itself")
+            }
+        }
+    }
+}
+
+impl Error for ArchiveError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{archive, done_before, refile, ArchiveError, ArchiveLocation};
+    use crate::parsing::document::Document;
+    use chrono::NaiveDate;
+    use std::path::{Path, PathBuf};
+
+    fn parse(path: &str, source: &str) -> Document {
+        Document::parse(Path::new(path), source.into(), &Default::default()).value
+    }
+
+    #[test]
+    fn refile_subtrees() {
+        let source = parse("/org/a.org", "* Work\n** TODO Deploy\n*** Notes\n* Home\n");
+        let target = parse("/org/b.org", "* Projects\n** Ops\n* Misc");
+        let deploy = &source.headings[0].children[0];
+        let moved = refile(&source, &[deploy], &target, Some("Projects / Ops")).unwrap();
+        assert_eq!(source.apply(&moved.source), "* Work\n* Home\n");
+        assert_eq!(
+            target.apply(&moved.target),
+            "* Projects\n** Ops\n*** TODO Deploy\n**** Notes\n* Misc"
+        );
+        let work = &source.headings[0];
+        let moved = refile(&source, &[work], &source, Some("Home")).unwrap();
+        let edits: Vec<_> = moved.source.into_iter().chain(moved.target).collect();
+        assert_eq!(
+            source.apply(&edits),
+            "* Home\n** Work\n*** TODO Deploy\n**** Notes\n"
+        );
+        assert_eq!(
+            refile(&source, &[work], &source, Some("Work / Deploy")),
+            Err(ArchiveError::IntoItself("Work".into()))
+        );
+        // Line endings are kept, and a subtree ending the file gets one
+        let source = parse(
+            "/org/a.org",
+            "* Work\r\n** TODO Deploy\r\n\tnotes\r\n** Last",
+        );
+        let target = parse("/org/b.org", "* Ops\n");
+        let subtrees = [
+            &source.headings[0].children[0],
+            &source.headings[0].children[1],
+        ];
+        let moved = refile(&source, &subtrees, &target, Some("Ops")).unwrap();
+        assert_eq!(source.apply(&moved.source), "* Work\r\n");
+        assert_eq!(
+            target.apply(&moved.target),
+            "* Ops\n** TODO Deploy\r\n\tnotes\r\n** Last\n"
+        );
+    }
+
+    #[test]
+    fn archive_done_headings() {
+        let source = parse(
+            "/org/a.org",
+            "#+CATEGORY: ops\n* Work\n** DONE Deploy\nCLOSED: [2023-08-01 Tue 10:00]\n*** DONE Step\nCLOSED: [2023-07-01 Sat]\n** DONE Recent\nCLOSED: [2023-09-05 Tue]\n",
+        );
+        let now = NaiveDate::from_ymd_opt(2023, 9, 6)
+            .unwrap()
+            .and_hms_opt(18, 30, 0)
+            .unwrap();
+        let old = done_before(&source, now - chrono::Duration::days(30));
+        assert_eq!(old.len(), 1);
+        let location: ArchiveLocation = "%s_archive::* Archive".parse().unwrap();
+        assert_eq!(
+            location.file_for(&source.path),
+            PathBuf::from("/org/a.org_archive")
+        );
+        let shared: ArchiveLocation = "archive.org::".parse().unwrap();
+        assert_eq!(shared.to_string(), "archive.org::");
+        assert_eq!(
+            shared.file_for(Path::new("org/a.org")),
+            PathBuf::from("org/archive.org")
+        );
+        let target = parse("/org/a.org_archive", "");
+        let moved = archive(&source, &old, &target, location.heading.as_deref(), now).unwrap();
+        assert_eq!(
+            source.apply(&moved.source),
+            "#+CATEGORY: ops\n* Work\n** DONE Recent\nCLOSED: [2023-09-05 Tue]\n"
+        );
+        assert_eq!(
+            target.apply(&moved.target),
+            "* Archive\n** DONE Deploy\nCLOSED: [2023-08-01 Tue 10:00]\n:PROPERTIES:\n:ARCHIVE_TIME: 2023-09-06 Wed 18:30\n:ARCHIVE_FILE: /org/a.org\n:ARCHIVE_OLPATH: Work\n:ARCHIVE_CATEGORY: ops\n:ARCHIVE_TODO: DONE\n:END:\n*** DONE Step\nCLOSED: [2023-07-01 Sat]\n"
+        );
+    }
+}
@@ -0,0 +1,271 @@
+//! Cache of the todos of the org files, kept between runs so that only the files that changed
+//! are parsed again. It is a single binary file, "$XDG_CACHE_HOME/orgparser/index.bin", holding
+//! the todos of every file with the modification time, size and hash of the content they were
+//! parsed from. A file with the same modification time and size is not read at all, unless
+//! it was cached right after it was modified: modification times come from a coarse clock, so
+//! such a file is read and its hash compared. Files with errors are not cached, so that their
+//! errors are reported on every run. The cache holds the files of the last run only.
+//!
+//! The files taken from the cache have no document, only todos: commands that edit files must
+//! parse them. Without the "cache" feature, the cache is always empty and never written.
+#[cfg(feature = "cache")]
+mod binary;
+
+use crate::parsing::document::Document;
+use crate::parsing::error::{ParseError, Parsed};
+use crate::parsing::file::{self, Origin, WriteError};
+use crate::parsing::keywords::TodoKeywords;
+use crate::parsing::TodoVec;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Files modified less than this before they were read are always read again.
+const RACY: Duration = Duration::from_secs(2);
+
+/// Todos of the org files saved by the last run, and where they are saved.
+#[derive(Debug, Default)]
+pub struct Cache {
+    /// None when the cache is disabled
+    path: Option<PathBuf>,
+    /// Entries of the cache file that were not read yet
+    entries: HashMap<PathBuf, Entry>,
+    /// State of the files read without errors, and when they were read
+    read: HashMap<PathBuf, (Origin, SystemTime)>,
+    /// Whether the cache file is out of date
+    changed: bool,
+}
+
+/// An org file read through the cache.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CachedFile {
+    /// Document of the file, None when its todos were taken from the cache
+    pub document: Option<Document>,
+    pub todos: TodoVec,
+}
+
+/// Todos of a file, with the state of the file they were parsed from.
+#[derive(Debug)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize, serde::Deserialize))]
+struct Entry<O = Origin, T = TodoVec> {
+    origin: O,
+    /// When the file was read
+    read_at: SystemTime,
+    todos: T,
+}
+
+/// Content of the cache file. Its entries are for the default keywords of the configuration.
+#[cfg(feature = "cache")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CacheFile<K, F> {
+    version: String,
+    keywords: K,
+    files: F,
+}
+
+impl Cache {
+    /// A cache that never holds anything.
+    pub fn disabled() -> Cache {
+        Cache::default()
+    }
+    /// Load the cache saved at `path` for files parsed with `keywords`. The cache is empty when
+    /// the file is missing or invalid, or was written by another version or for other keywords.
+    pub fn load(path: PathBuf, keywords: &TodoKeywords) -> Cache {
+        #[cfg(feature = "cache")]
+        {
+            let entries = std::fs::read(&path)
+                .ok()
+                .and_then(|bytes| {
+                    binary::from_slice::<CacheFile<TodoKeywords, HashMap<PathBuf, Entry>>>(&bytes)
+                        .ok()
+                })
+                .filter(|c| c.version == env!("CARGO_PKG_VERSION") && c.keywords == *keywords)
+                .map(|c| c.files)
+                .unwrap_or_default();
+            Cache {
+                path: Some(path),
+                entries,
+                read: HashMap::new(),
+                changed: false,
+            }
+        }
+        #[cfg(not(feature = "cache"))]
+        {
+            let _ = (path, keywords);
+            Cache::disabled()
+        }
+    }
+    /// Read the todos of an org file, from the cache when the file did not change, else by
+    /// parsing it. Returns an error if the file cannot be read at all, like `read_document`.
+    pub async fn read(
+        &mut self,
+        path: &Path,
+        keywords: &TodoKeywords,
+    ) -> Result<Parsed<CachedFile>, ParseError> {
+        let read_error = |source| ParseError::Read {
+            path: path.to_path_buf(),
+            source,
+        };
+        let mut entry = self.entries.remove(path);
+        let unchanged = match &entry {
+            Some(entry) => {
+                let metadata = tokio::fs::metadata(path).await.map_err(read_error)?;
+                let modified = metadata.modified().ok();
+                let racy = modified.is_none_or(|m| m + RACY > entry.read_at);
+                entry.origin.modified == modified && entry.origin.len == metadata.len() && !racy
+            }
+            None => false,
+        };
+        if let Some(entry) = entry.take_if(|_| unchanged) {
+            self.read
+                .insert(path.to_path_buf(), (entry.origin, entry.read_at));
+            return Ok(Parsed::new(CachedFile {
+                document: None,
+                todos: entry.todos,
+            }));
+        }
+        let read_at = SystemTime::now();
+        let (source, origin) = file::read_async(path).await.map_err(read_error)?;
+        self.changed = true;
+        if let Some(entry) = entry.filter(|e| e.origin.hash == origin.hash) {
+            self.read.insert(path.to_path_buf(), (origin, read_at));
+            return Ok(Parsed::new(CachedFile {
+                document: None,
+                todos: entry.todos,
+            }));
+        }
+        let mut parsed = Document::parse(path, source, keywords);
+        parsed.value.origin = Some(origin.clone());
+        if parsed.errors.is_empty() {
+            self.read.insert(path.to_path_buf(), (origin, read_at));
+        } else {
+            self.read.remove(path);
+        }
+        Ok(parsed.map(|document| CachedFile {
+            todos: document.todos(),
+            document: Some(document),
+        }))
+    }
+    /// Write the cache with the todos of `files`, e.g. the ones currently indexed, if it changed
+    /// since it was loaded. Files that were not read by this cache, or had errors, are left
+    /// out. `keywords` must be the ones it was loaded with.
+    pub fn save<'a, I>(&mut self, files: I, keywords: &TodoKeywords) -> Result<(), WriteError>
+    where
+        I: IntoIterator<Item = (&'a Path, &'a TodoVec)>,
+    {
+        let Some(path) = self.path.as_ref() else {
+            return Ok(());
+        };
+        // Entries that were not read are dropped, e.g. the ones of deleted files
+        if !self.changed && self.entries.is_empty() {
+            return Ok(());
+        }
+        #[cfg(feature = "cache")]
+        {
+            let io_error = |e| WriteError::Io(path.clone(), e);
+            let files: HashMap<&Path, Entry<&Origin, &TodoVec>> = files
+                .into_iter()
+                .filter_map(|(file, todos)| {
+                    let (origin, read_at) = self.read.get(file)?;
+                    let read_at = *read_at;
+                    Some((
+                        file,
+                        Entry {
+                            origin,
+                            read_at,
+                            todos,
+                        },
+                    ))
+                })
+                .collect();
+            let cache = CacheFile {
+                version: String::from(env!("CARGO_PKG_VERSION")),
+                keywords,
+                files,
+            };
+            let bytes = binary::to_vec(&cache)
+                .map_err(|e| io_error(std::io::Error::new(std::io::ErrorKind::InvalidData, e)))?;
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir).map_err(|e| WriteError::Io(dir.into(), e))?;
+            }
+            file::atomic_write(path, &bytes).map_err(io_error)?;
+        }
+        #[cfg(not(feature = "cache"))]
+        let _ = (path, files, keywords);
+        self.entries.clear();
+        self.changed = false;
+        Ok(())
+    }
+}
+
+/// Default location of the cache: "$XDG_CACHE_HOME/orgparser/index.bin", or
+/// "~/.cache/orgparser/index.bin".
+pub fn default_path() -> Option<PathBuf> {
+    let cache_home = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))?;
+    Some(cache_home.join("orgparser").join("index.bin"))
+}
+
+#[cfg(all(test, feature = "cache"))]
+mod tests {
+    use super::Cache;
+    use crate::parsing::keywords::{KeywordSequence, TodoKeywords};
+    use std::fs;
+    use std::path::Path;
+    use std::time::{Duration, SystemTime};
+
+    #[tokio::test]
+    async fn cached_todos() {
+        let dir = std::env::temp_dir().join(format!("orgparser-cache-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (file, path) = (dir.join("a.org"), dir.join("cache/index.bin"));
+        fs::write(&file, "* TODO A\nSCHEDULED: <2023-09-05 Tue>\n").unwrap();
+        let keywords = TodoKeywords::default();
+        let mut cache = Cache::load(path.clone(), &keywords);
+        let read = cache.read(&file, &keywords).await.unwrap().value;
+        assert!(read.document.is_some());
+        cache
+            .save([(file.as_path(), &read.todos)], &keywords)
+            .unwrap();
+        assert!(path.exists());
+
+        // Pretend the file was read long after it was modified, so that it is not read again
+        let mut cache = Cache::load(path.clone(), &keywords);
+        assert_eq!(cache.entries.len(), 1);
+        for entry in cache.entries.values_mut() {
+            entry.read_at = SystemTime::now() + Duration::from_secs(10);
+        }
+        let cached = cache.read(&file, &keywords).await.unwrap().value;
+        assert_eq!((cached.document, &cached.todos), (None, &read.todos));
+        assert_eq!(read.todos.len(), 1);
+        assert!(!cache.changed);
+
+        // A change of size is seen, and so is a change of content while the entry is racy
+        fs::write(&file, "* TODO Bb\nSCHEDULED: <2023-09-05 Tue>\n").unwrap();
+        let changed = cache.read(&file, &keywords).await.unwrap().value;
+        assert_eq!(changed.todos[0].title(), "Bb");
+        cache
+            .save([(file.as_path(), &changed.todos)], &keywords)
+            .unwrap();
+        let mut cache = Cache::load(path.clone(), &keywords);
+        fs::write(&file, "* TODO Cc\nSCHEDULED: <2023-09-05 Tue>\n").unwrap();
+        let changed = cache.read(&file, &keywords).await.unwrap().value;
+        assert_eq!(changed.todos[0].title(), "Cc");
+
+        // Unread entries are dropped, and other keywords make another cache
+        cache
+            .save([(file.as_path(), &changed.todos)], &keywords)
+            .unwrap();
+        let other = TodoKeywords {
+            sequences: vec![KeywordSequence::parse("NEXT | DONE").unwrap()],
+        };
+        assert!(Cache::load(path.clone(), &other).entries.is_empty());
+        let mut cache = Cache::load(path.clone(), &keywords);
+        assert_eq!(cache.entries.len(), 1);
+        cache.save([] as [(&Path, _); 0], &keywords).unwrap();
+        assert!(Cache::load(path, &keywords).entries.is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
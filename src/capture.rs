@@ -0,0 +1,236 @@
+//! Capture of new entries from the command line, with templates like org-capture.
+//! A template is the text of an entry with placeholders:
+//! - "%?": the text given on the command line;
+//! - "%U", "%u": inactive timestamp of now, with and without time; "%T", "%t": active ones;
+//! - "%a": link given on the command line, as "[[link]]";
+//! - "%^{Name}", "%^{Name|default|other}": a prompted field, and "%\1" its value again;
+//! - "%%": a percent sign.
+//!
+//! The level of the entry is adjusted to its target: the first sub-level of the target
+//! heading, or the top level at the end of the file.
+use crate::parsing::document::{Document, Heading};
+use crate::parsing::edit::{append_entry, Edit};
+use crate::parsing::is_heading;
+use crate::parsing::timestamp::{Moment, Timestamp};
+use chrono::NaiveDateTime;
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+
+/// A capture template of the configuration.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CaptureTemplate {
+    /// File the entries are added to. A relative path is relative to the first org root
+    pub file: PathBuf,
+    /// Outline path of the heading the entries are added under. Example: "Work / Tasks"
+    pub heading: Option<String>,
+    pub template: String,
+}
+
+/// What the placeholders of a template are replaced by.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Context {
+    pub now: NaiveDateTime,
+    /// Replaces "%?"
+    pub text: String,
+    /// Replaces "%a"
+    pub link: Option<String>,
+}
+
+/// Error returned when an entry cannot be captured.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CaptureError {
+    /// A prompted field has no value and no default
+    MissingField(String),
+    /// The template does not start with a heading
+    NotAnEntry,
+    /// No heading has the outline path of the template
+    HeadingNotFound(String),
+}
+
+impl CaptureTemplate {
+    /// The template with the built-in placeholders of `context` replaced. `prompt` is called
+    /// with the name and the default value of every prompted field, and returns its value.
+    pub fn expand<F>(&self, context: &Context, mut prompt: F) -> Result<String, CaptureError>
+    where
+        F: FnMut(&str, Option<&str>) -> Option<String>,
+    {
+        let timestamp = |active: bool, time: bool| {
+            let mut ts = Timestamp::inactive(context.now);
+            ts.active = active;
+            if !time {
+                ts.start = Moment {
+                    date: ts.start.date,
+                    time: None,
+                };
+            }
+            ts.to_string()
+        };
+        let mut fields: Vec<String> = vec![];
+        let mut text = String::with_capacity(self.template.len());
+        let mut rest = self.template.as_str();
+        while let Some(index) = rest.find('%') {
+            text.push_str(&rest[..index]);
+            rest = &rest[index + 1..];
+            let mut chars = rest.chars();
+            let (value, length) = match chars.next() {
+                Some('?') => (context.text.clone(), 1),
+                Some('U') => (timestamp(false, true), 1),
+                Some('u') => (timestamp(false, false), 1),
+                Some('T') => (timestamp(true, true), 1),
+                Some('t') => (timestamp(true, false), 1),
+                Some('a') => (
+                    context
+                        .link
+                        .as_ref()
+                        .map_or(String::new(), |l| format!("[[{l}]]")),
+                    1,
+                ),
+                Some('%') => (String::from("%"), 1),
+                Some('\\') => {
+                    let digits: String = chars.take_while(char::is_ascii_digit).collect();
+                    let field = digits
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|n| fields.get(n.checked_sub(1)?));
+                    match field {
+                        Some(value) => (value.clone(), 1 + digits.len()),
+                        None => (String::from("%"), 0),
+                    }
+                }
+                Some('^') if rest[1..].starts_with('{') => match rest.find('}') {
+                    Some(end) => {
+                        let spec = &rest[2..end];
+                        let (name, default) = match spec.split_once('|') {
+                            Some((name, options)) => (name, options.split('|').next()),
+                            None => (spec, None),
+                        };
+                        let value = prompt(name, default)
+                            .or(default.map(String::from))
+                            .ok_or_else(|| CaptureError::MissingField(String::from(name)))?;
+                        fields.push(value.clone());
+                        (value, end + 1)
+                    }
+                    None => (String::from("%"), 0),
+                },
+                // Unknown placeholders are kept as they are
+                _ => (String::from("%"), 0),
+            };
+            text.push_str(&value);
+            rest = &rest[length..];
+        }
+        text.push_str(rest);
+        Ok(text)
+    }
+}
+
+/// Edit adding the expanded template `entry` to `doc`, under the heading of the template if
+/// it has one. The headings of the entry are shifted to the level of the target.
+pub fn capture(
+    doc: &Document,
+    template: &CaptureTemplate,
+    entry: &str,
+) -> Result<Edit, CaptureError> {
+    let parent: Option<&Heading> = match &template.heading {
+        Some(path) => Some(
+            doc.find(path)
+                .ok_or_else(|| CaptureError::HeadingNotFound(path.clone()))?,
+        ),
+        None => None,
+    };
+    let entry = entry.trim_end_matches(['\n', '\r']);
+    let first = entry.lines().next().filter(|l| is_heading(l));
+    let stars = |line: &str| line.len() - line.trim_start_matches('*').len();
+    let first_level = first.map(stars).ok_or(CaptureError::NotAnEntry)?;
+    let level = parent.map_or(1, |p| p.level + 1);
+    let newline = doc.newline();
+    let lines: Vec<String> = entry
+        .lines()
+        .map(|line| match is_heading(line) {
+            true => {
+                let shifted = (stars(line) + level).saturating_sub(first_level).max(1);
+                format!("{}{}", "*".repeat(shifted), line.trim_start_matches('*'))
+            }
+            false => String::from(line),
+        })
+        .collect();
+    Ok(append_entry(doc, parent, &lines.join(newline)))
+}
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CaptureError::MissingField(name) => write!(f, "no value for the field {name}"),
+            CaptureError::NotAnEntry => write!(f, "the template does not start with a heading"),
+            CaptureError::HeadingNotFound(path) => write!(f, "no heading {path}"),
+        }
+    }
+}
+
+impl Error for CaptureError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{capture, CaptureError, CaptureTemplate, Context};
+    use crate::parsing::document::Document;
+    use chrono::NaiveDate;
+    use std::path::{Path, PathBuf};
+
+    fn template(heading: Option<&str>, template: &str) -> CaptureTemplate {
+        CaptureTemplate {
+            file: PathBuf::from("inbox.org"),
+            heading: heading.map(String::from),
+            template: String::from(template),
+        }
+    }
+
+    #[test]
+    fn expand_placeholders() {
+        let context = Context {
+            now: NaiveDate::from_ymd_opt(2023, 9, 6)
+                .unwrap()
+                .and_hms_opt(18, 30, 0)
+                .unwrap(),
+            text: String::from("Call Bob"),
+            link: Some(String::from("https://example.com")),
+        };
+        let t = template(
+            None,
+            "* TODO %? %^{Where|office|home} :%\\1:\nSCHEDULED: %t\n%U %u %T %a 100%% %x %^{Who}",
+        );
+        let expanded = t.expand(&context, |name, _| (name == "Who").then(|| "me".into()));
+        assert_eq!(
+            expanded.unwrap(),
+            "* TODO Call Bob office :office:\nSCHEDULED: <2023-09-06 Wed>\n[2023-09-06 Wed 18:30] [2023-09-06 Wed] <2023-09-06 Wed 18:30> [[https://example.com]] 100% %x me"
+        );
+        assert_eq!(
+            t.expand(&context, |_, _| None),
+            Err(CaptureError::MissingField("Who".into()))
+        );
+    }
+
+    #[test]
+    fn capture_entries() {
+        let doc = Document::parse(
+            Path::new("inbox.org"),
+            "* Work\n** Tasks\n* Home".into(),
+            &Default::default(),
+        )
+        .value;
+        let entry = "* TODO Call\n** Sub-task\nNotes\n";
+        let edit = capture(&doc, &template(Some("Work / Tasks"), ""), entry).unwrap();
+        assert_eq!(
+            doc.apply(&[edit]),
+            "* Work\n** Tasks\n*** TODO Call\n**** Sub-task\nNotes\n* Home"
+        );
+        let edit = capture(&doc, &template(None, ""), entry).unwrap();
+        assert_eq!(
+            doc.apply(&[edit]),
+            "* Work\n** Tasks\n* Home\n* TODO Call\n** Sub-task\nNotes"
+        );
+        assert_eq!(
+            capture(&doc, &template(None, ""), "TODO Call"),
+            Err(CaptureError::NotAnEntry)
+        );
+    }
+}
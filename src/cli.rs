@@ -0,0 +1,178 @@
+//! Command line interface of orgparser.
+use chrono::NaiveDate;
+use clap::{Parser, Subcommand};
+use orgparser::agenda::Span;
+use orgparser::archive::ArchiveLocation;
+use orgparser::output::Format;
+use orgparser::report::ClockGroup;
+use orgparser::schedule::DateInput;
+use orgparser::select::Selector;
+use orgparser::sink::SinkConfig;
+use std::path::PathBuf;
+
+/// Reminders for emacs org-mode files.
+#[derive(Debug, Parser)]
+#[command(name = "orgparser", version)]
+pub struct Cli {
+    /// Configuration file [default: $XDG_CONFIG_HOME/orgparser/config.toml]
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+    /// Org directory to read instead of the org roots of the configuration. Can be repeated
+    #[arg(long = "org-dir", global = true)]
+    pub org_dirs: Vec<PathBuf>,
+    /// Print the changes of the files as a unified diff instead of writing them
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+    /// Write the files even when Emacs has unsaved changes of them
+    #[arg(long, global = true)]
+    pub force: bool,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// List the todos, sorted by date (default)
+    List {
+        /// Only list the todos with this tag, set on the heading, inherited or through a tag
+        /// group. Can be repeated, every tag must match
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// Output format: "text" ("title,date" lines), "json" or "ndjson"
+        #[arg(long, default_value = "text")]
+        format: Format,
+    },
+    /// Show the agenda, grouped by day
+    Agenda {
+        /// Days to show: "day", "week" (from Monday) or a number of days
+        #[arg(long, default_value = "week")]
+        span: Span,
+        /// First day to show, as YYYY-MM-DD [default: today, or Monday for a week]
+        #[arg(long)]
+        start: Option<NaiveDate>,
+    },
+    /// Run the reminder daemon
+    Daemon {
+        /// Sink to use instead of the sinks of the configuration, as "stdout", "desktop" or
+        /// "command:PROGRAM ARGS...". Can be repeated
+        #[arg(long = "sink")]
+        sinks: Vec<SinkConfig>,
+        /// Minutes before an event at which a reminder is fired. Can be repeated
+        #[arg(long = "lead")]
+        lead_minutes: Vec<u32>,
+    },
+    /// Check the configuration and the org files
+    Check,
+    /// Sum the clocked time over a date range
+    ClockReport {
+        /// First day of the range, as YYYY-MM-DD [default: first day of the current month]
+        #[arg(long)]
+        from: Option<NaiveDate>,
+        /// Last day of the range, included, as YYYY-MM-DD [default: today]
+        #[arg(long)]
+        to: Option<NaiveDate>,
+        /// Sum the time by "file", "tag" or "heading"
+        #[arg(long = "by", default_value = "file")]
+        group: ClockGroup,
+    },
+    /// Export the scheduled items and deadlines as an iCalendar file
+    ExportIcs {
+        /// File to write, "-" for the standard output
+        #[arg(long, short, default_value = "orgparser.ics")]
+        output: PathBuf,
+        /// Name of the calendar shown by calendar apps
+        #[arg(long, default_value = "orgparser")]
+        name: String,
+    },
+    /// Import the events of an iCalendar file into an org file, updating the headings
+    /// imported before
+    ImportIcs {
+        /// iCalendar file to read
+        calendar: PathBuf,
+        /// Org file to write the events to
+        #[arg(long)]
+        file: PathBuf,
+        /// Outline path of the heading to add the events under, e.g. "Work / Meetings"
+        /// [default: the end of the file]
+        #[arg(long)]
+        heading: Option<String>,
+    },
+    /// Mark a heading done. A repeating heading is shifted to its next occurrence instead
+    Done {
+        /// ID or outline path of the heading, e.g. "Work / Deploy", or FILE:LINE
+        selector: Selector,
+    },
+    /// Set the scheduled date of a heading
+    Schedule {
+        /// ID or outline path of the heading, e.g. "Work / Deploy", or FILE:LINE
+        selector: Selector,
+        /// New date, e.g. "2023-10-01", "+2d 14:00", "++1w" (from the current date) or "fri"
+        #[arg(allow_hyphen_values = true)]
+        date: DateInput,
+        /// Note the previous date in the logbook, whatever the configuration says
+        #[arg(long)]
+        log: bool,
+    },
+    /// Set the deadline of a heading
+    Deadline {
+        /// ID or outline path of the heading, e.g. "Work / Deploy", or FILE:LINE
+        selector: Selector,
+        /// New date, e.g. "2023-10-01", "+2d 14:00", "++1w" (from the current date) or "fri"
+        #[arg(allow_hyphen_values = true)]
+        date: DateInput,
+        /// Note the previous deadline in the logbook, whatever the configuration says
+        #[arg(long)]
+        log: bool,
+    },
+    /// Move a heading and its sub-headings under another heading, or to the end of a file
+    Refile {
+        /// ID or outline path of the heading, e.g. "Work / Deploy", or FILE:LINE
+        selector: Selector,
+        /// Org file to move the heading to [default: the file of the heading]
+        #[arg(long)]
+        file: Option<PathBuf>,
+        /// Outline path of the heading to move the heading under, e.g. "Projects / Ops",
+        /// added when missing [default: the end of the file]
+        #[arg(long)]
+        heading: Option<String>,
+    },
+    /// Move a heading and its sub-headings to the archive, or every heading done long ago
+    Archive {
+        /// ID or outline path of the heading, e.g. "Work / Deploy", or FILE:LINE
+        #[arg(required_unless_present = "done_before_days")]
+        selector: Option<Selector>,
+        /// Archive every heading of the org files marked done more than this many days ago
+        #[arg(
+            long = "done-older-than",
+            value_name = "DAYS",
+            conflicts_with = "selector"
+        )]
+        done_before_days: Option<u32>,
+        /// Where to archive, as FILE::HEADING, e.g. "%s_archive::" or "::* Archive"
+        /// [default: archive_location of the configuration]
+        #[arg(long)]
+        location: Option<ArchiveLocation>,
+    },
+    /// Add an entry from a capture template of the configuration
+    Capture {
+        /// Key of the template
+        #[arg(long, short, default_value = "t")]
+        template: String,
+        /// Link replacing "%a" in the template
+        #[arg(long)]
+        link: Option<String>,
+        /// Value of a prompted field, as NAME=VALUE. Can be repeated. The fields without value
+        /// are asked on a terminal, else take their default
+        #[arg(long = "field", value_parser = parse_field)]
+        fields: Vec<(String, String)>,
+        /// Text replacing "%?" in the template
+        text: Vec<String>,
+    },
+}
+
+/// Parse "NAME=VALUE".
+fn parse_field(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(name, value)| (String::from(name), String::from(value)))
+        .ok_or_else(|| format!("{s} (expected NAME=VALUE)"))
+}
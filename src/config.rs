@@ -0,0 +1,432 @@
+//! Configuration of orgparser, read from "$XDG_CONFIG_HOME/orgparser/config.toml".
+//! Every setting can be overridden by an environment variable, and the command line wins over
+//! both. Example of configuration file:
+//!
+//! ```toml
+//! org_roots = ["~/org"]
+//! exclude = ["~/org/archive"]
+//! lead_minutes = [30, 5]
+//! sinks = ["desktop", { command = "say", args = ["{title} in {minutes} minutes"] }]
+//! todo_keywords = ["TODO NEXT WAITING | DONE CANCELLED"]
+//! log_done = true
+//! log_reschedule = true
+//! archive_location = "%s_archive::"
+//! cache = true
+//!
+//! [templates.m]
+//! file = "work.org"
+//! heading = "Meetings"
+//! template = "* %^{With} about %?\nSCHEDULED: %^{When}\n%U"
+//! ```
+use crate::archive::ArchiveLocation;
+use crate::capture::CaptureTemplate;
+use crate::parsing::keywords::{KeywordSequence, TodoKeywords};
+use crate::sink::{CommandSink, SinkConfig, SinkConfigError};
+use chrono::Duration;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Environment variable holding the path of the configuration file.
+pub const CONFIG_ENV: &str = "ORGPARSER_CONFIG";
+/// Environment variable overriding the org roots, separated like $PATH.
+pub const ORG_ROOTS_ENV: &str = "ORGPARSER_ORG_ROOTS";
+/// Environment variable overriding the excluded paths, separated like $PATH.
+pub const EXCLUDE_ENV: &str = "ORGPARSER_EXCLUDE";
+/// Environment variable overriding the lead times, separated by commas. Example: "30,5"
+pub const LEAD_MINUTES_ENV: &str = "ORGPARSER_LEAD_MINUTES";
+/// Environment variable overriding the sinks, separated by semicolons.
+/// Example: "desktop;command:notify-send {title}"
+pub const SINKS_ENV: &str = "ORGPARSER_SINKS";
+/// Environment variable overriding the keyword sequences, separated by semicolons.
+/// Example: "TODO NEXT | DONE;BUG | FIXED"
+pub const TODO_KEYWORDS_ENV: &str = "ORGPARSER_TODO_KEYWORDS";
+/// Environment variable overriding whether "CLOSED:" is added, as "true" or "false".
+pub const LOG_DONE_ENV: &str = "ORGPARSER_LOG_DONE";
+/// Environment variable overriding whether reschedules are logged, as "true" or "false".
+pub const LOG_RESCHEDULE_ENV: &str = "ORGPARSER_LOG_RESCHEDULE";
+/// Environment variable overriding where subtrees are archived. Example: "::* Archive"
+pub const ARCHIVE_LOCATION_ENV: &str = "ORGPARSER_ARCHIVE_LOCATION";
+/// Environment variable enabling the cache of the parsed files: "true" or "false"
+pub const CACHE_ENV: &str = "ORGPARSER_CACHE";
+
+/// The configuration of orgparser.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Config {
+    /// Directories walked to find the org files
+    pub org_roots: Vec<PathBuf>,
+    /// Files and directories that are never read
+    pub exclude: Vec<PathBuf>,
+    /// Reminders are fired these durations before each event
+    pub lead_times: Vec<Duration>,
+    /// Ways the reminders are delivered
+    pub sinks: Vec<SinkConfig>,
+    /// Keyword sequences of the files without "#+TODO:" line
+    pub todo_keywords: TodoKeywords,
+    /// Add a "CLOSED:" timestamp to the headings marked done, like org-log-done
+    pub log_done: bool,
+    /// Add a note to the logbook when a scheduled date or a deadline is changed, like
+    /// org-log-reschedule
+    pub log_reschedule: bool,
+    /// Where subtrees are archived, like org-archive-location
+    pub archive_location: ArchiveLocation,
+    /// Capture templates by key. The template "t" adds a todo to "inbox.org" by default
+    pub templates: BTreeMap<String, CaptureTemplate>,
+    /// Keep the todos in "$XDG_CACHE_HOME/orgparser" to parse only the changed files. Needs a
+    /// build with the "cache" feature, which is not a default one, and defaults to true with it
+    pub cache: bool,
+}
+
+/// Error returned when the configuration cannot be loaded.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The configuration file cannot be read
+    Io(PathBuf, io::Error),
+    /// The configuration file is not valid TOML, or has unknown settings
+    Toml(PathBuf, toml::de::Error),
+    /// A sink cannot be used
+    Sink(SinkConfigError),
+    /// A setting has an invalid value
+    Invalid(&'static str, String),
+}
+
+/// The configuration file, as written by the user. Every setting is optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    org_roots: Option<Vec<String>>,
+    exclude: Option<Vec<String>>,
+    lead_minutes: Option<Vec<i64>>,
+    sinks: Option<Vec<SinkEntry>>,
+    todo_keywords: Option<Vec<String>>,
+    log_done: Option<bool>,
+    log_reschedule: Option<bool>,
+    archive_location: Option<String>,
+    cache: Option<bool>,
+    templates: Option<BTreeMap<String, TemplateEntry>>,
+}
+
+/// A capture template in the configuration file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TemplateEntry {
+    file: String,
+    heading: Option<String>,
+    template: String,
+}
+
+/// A sink in the configuration file: either a name ("desktop", "command:say {title}") or a
+/// command with its arguments.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum SinkEntry {
+    Name(String),
+    Command {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            org_roots: vec![expand_home("~/org")],
+            exclude: vec![],
+            lead_times: vec![Duration::minutes(30), Duration::minutes(5)],
+            sinks: vec![SinkConfig::Stdout],
+            todo_keywords: TodoKeywords::default(),
+            log_done: false,
+            log_reschedule: false,
+            archive_location: ArchiveLocation::default(),
+            templates: BTreeMap::from([(
+                String::from("t"),
+                CaptureTemplate {
+                    file: PathBuf::from("inbox.org"),
+                    heading: None,
+                    template: String::from("* TODO %?\n%U"),
+                },
+            )]),
+            cache: cfg!(feature = "cache"),
+        }
+    }
+}
+
+impl Config {
+    /// Load the configuration from `path`, or from the default location when None, and apply
+    /// the environment variables. A missing file at the default location is not an error.
+    pub fn load(path: Option<&Path>) -> Result<Config, ConfigError> {
+        let env = |name: &str| std::env::var(name).ok();
+        let explicit = path
+            .map(Path::to_path_buf)
+            .or_else(|| env(CONFIG_ENV).map(PathBuf::from));
+        let file = match explicit {
+            Some(path) => Some(read_config_file(&path)?),
+            None => match default_path() {
+                Some(path) if path.exists() => Some(read_config_file(&path)?),
+                _ => None,
+            },
+        };
+        let mut config = Config::default();
+        if let Some(file) = file {
+            config.apply_file(file)?;
+        }
+        config.apply_env(env)?;
+        Ok(config)
+    }
+    /// Parse a configuration file, on top of the default configuration.
+    pub fn from_toml(text: &str) -> Result<Config, ConfigError> {
+        let file: ConfigFile =
+            toml::from_str(text).map_err(|e| ConfigError::Toml(PathBuf::new(), e))?;
+        let mut config = Config::default();
+        config.apply_file(file)?;
+        Ok(config)
+    }
+    fn apply_file(&mut self, file: ConfigFile) -> Result<(), ConfigError> {
+        if let Some(roots) = file.org_roots {
+            self.org_roots = roots.iter().map(|p| expand_home(p)).collect();
+        }
+        if let Some(exclude) = file.exclude {
+            self.exclude = exclude.iter().map(|p| expand_home(p)).collect();
+        }
+        if let Some(minutes) = file.lead_minutes {
+            self.lead_times = lead_times(&minutes)?;
+        }
+        if let Some(sinks) = file.sinks {
+            self.sinks = sinks
+                .into_iter()
+                .map(|entry| match entry {
+                    SinkEntry::Name(name) => name.parse().map_err(ConfigError::Sink),
+                    SinkEntry::Command { command, args } => Ok(SinkConfig::Command(CommandSink {
+                        program: command,
+                        args,
+                    })),
+                })
+                .collect::<Result<_, _>>()?;
+        }
+        if let Some(sequences) = file.todo_keywords {
+            self.todo_keywords = todo_keywords(sequences.iter().map(String::as_str))?;
+        }
+        if let Some(log_done) = file.log_done {
+            self.log_done = log_done;
+        }
+        if let Some(log_reschedule) = file.log_reschedule {
+            self.log_reschedule = log_reschedule;
+        }
+        if let Some(location) = file.archive_location {
+            let Ok(location) = location.parse();
+            self.archive_location = location;
+        }
+        if let Some(cache) = file.cache {
+            self.cache = cache;
+        }
+        for (key, entry) in file.templates.into_iter().flatten() {
+            let template = CaptureTemplate {
+                file: expand_home(&entry.file),
+                heading: entry.heading,
+                template: entry.template,
+            };
+            self.templates.insert(key, template);
+        }
+        Ok(())
+    }
+    /// Override the settings with the environment variables returned by `env`.
+    pub fn apply_env<F>(&mut self, env: F) -> Result<(), ConfigError>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        if let Some(roots) = env(ORG_ROOTS_ENV) {
+            self.org_roots = std::env::split_paths(&roots).collect();
+        }
+        if let Some(exclude) = env(EXCLUDE_ENV) {
+            self.exclude = std::env::split_paths(&exclude).collect();
+        }
+        if let Some(minutes) = env(LEAD_MINUTES_ENV) {
+            let minutes = minutes
+                .split(',')
+                .map(|m| m.trim().parse())
+                .collect::<Result<Vec<i64>, _>>()
+                .map_err(|_| ConfigError::Invalid(LEAD_MINUTES_ENV, minutes.clone()))?;
+            self.lead_times = lead_times(&minutes)?;
+        }
+        if let Some(sinks) = env(SINKS_ENV) {
+            self.sinks = sinks
+                .split(';')
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .map_err(ConfigError::Sink)?;
+        }
+        if let Some(sequences) = env(TODO_KEYWORDS_ENV) {
+            self.todo_keywords = todo_keywords(sequences.split(';'))?;
+        }
+        if let Some(log_done) = env(LOG_DONE_ENV) {
+            self.log_done = flag(LOG_DONE_ENV, &log_done)?;
+        }
+        if let Some(log_reschedule) = env(LOG_RESCHEDULE_ENV) {
+            self.log_reschedule = flag(LOG_RESCHEDULE_ENV, &log_reschedule)?;
+        }
+        if let Some(location) = env(ARCHIVE_LOCATION_ENV) {
+            let Ok(location) = location.parse();
+            self.archive_location = location;
+        }
+        if let Some(cache) = env(CACHE_ENV) {
+            self.cache = flag(CACHE_ENV, &cache)?;
+        }
+        Ok(())
+    }
+}
+
+/// Default location of the configuration file: "$XDG_CONFIG_HOME/orgparser/config.toml",
+/// or "~/.config/orgparser/config.toml".
+pub fn default_path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config_home.join("orgparser").join("config.toml"))
+}
+
+/// Replace a leading "~" by the home directory.
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix('~'), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            Path::new(&home).join(rest.trim_start_matches('/'))
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
+fn read_config_file(path: &Path) -> Result<ConfigFile, ConfigError> {
+    let text = std::fs::read_to_string(path).map_err(|e| ConfigError::Io(path.into(), e))?;
+    toml::from_str(&text).map_err(|e| ConfigError::Toml(path.into(), e))
+}
+
+fn lead_times(minutes: &[i64]) -> Result<Vec<Duration>, ConfigError> {
+    minutes
+        .iter()
+        .map(|m| match Duration::try_minutes(*m) {
+            Some(lead) if *m >= 0 => Ok(lead),
+            _ => Err(ConfigError::Invalid("lead_minutes", m.to_string())),
+        })
+        .collect()
+}
+
+/// Parse a boolean environment variable, "true" or "false".
+fn flag(name: &'static str, value: &str) -> Result<bool, ConfigError> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| ConfigError::Invalid(name, value.into()))
+}
+
+fn todo_keywords<'a, I>(sequences: I) -> Result<TodoKeywords, ConfigError>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let sequences = sequences
+        .map(|s| KeywordSequence::parse(s).ok_or(ConfigError::Invalid("todo_keywords", s.into())))
+        .collect::<Result<Vec<_>, _>>()?;
+    if sequences.is_empty() {
+        return Err(ConfigError::Invalid("todo_keywords", String::new()));
+    }
+    Ok(TodoKeywords { sequences })
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, e) => write!(f, "cannot read {}: {e}", path.display()),
+            ConfigError::Toml(path, e) => write!(f, "invalid config {}: {e}", path.display()),
+            ConfigError::Sink(e) => write!(f, "{e}"),
+            ConfigError::Invalid(name, value) => write!(f, "invalid value for {name}: {value:?}"),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, ConfigError};
+    use crate::parsing::keywords::KeywordState;
+    use crate::sink::SinkConfig;
+    use chrono::Duration;
+    use std::path::PathBuf;
+
+    #[test]
+    fn config_file() {
+        let config = Config::from_toml(
+            r#"
+            org_roots = ["/data/org", "/data/work"]
+            exclude = ["/data/org/archive"]
+            lead_minutes = [15]
+            sinks = ["stdout", { command = "say", args = ["{title} now"] }]
+            todo_keywords = ["TODO NEXT | DONE"]
+            log_done = true
+            archive_location = "::* Archive"
+            cache = false
+
+            [templates.m]
+            file = "~/org/work.org"
+            heading = "Meetings"
+            template = "* %?"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.org_roots.len(), 2);
+        assert_eq!(config.exclude, [PathBuf::from("/data/org/archive")]);
+        assert_eq!(config.lead_times, [Duration::minutes(15)]);
+        assert_eq!(config.sinks.len(), 2);
+        assert_eq!(
+            config.todo_keywords.state("NEXT"),
+            Some(KeywordState::Active)
+        );
+        assert!(config.log_done);
+        assert_eq!(config.archive_location.heading.as_deref(), Some("Archive"));
+        assert!(!config.cache);
+        assert_eq!(config.templates.len(), 2);
+        assert_eq!(config.templates["m"].heading.as_deref(), Some("Meetings"));
+    }
+
+    #[test]
+    fn defaults_and_errors() {
+        let config = Config::from_toml("").unwrap();
+        assert_eq!(config, Config::default());
+        assert!(matches!(
+            Config::from_toml("unknown = 1"),
+            Err(ConfigError::Toml(..))
+        ));
+        assert!(matches!(
+            Config::from_toml("lead_minutes = [-5]"),
+            Err(ConfigError::Invalid(..))
+        ));
+        assert!(matches!(
+            Config::from_toml(r#"sinks = ["pager"]"#),
+            Err(ConfigError::Sink(..))
+        ));
+    }
+
+    #[test]
+    fn environment_overrides() {
+        let mut config = Config::default();
+        let env = |name: &str| match name {
+            super::ORG_ROOTS_ENV => Some(String::from("/a:/b")),
+            super::LEAD_MINUTES_ENV => Some(String::from("10, 1")),
+            super::SINKS_ENV => Some(String::from("stdout;command:notify-send {title}")),
+            super::LOG_DONE_ENV => Some(String::from("true")),
+            _ => None,
+        };
+        config.apply_env(env).unwrap();
+        assert_eq!(config.org_roots, [PathBuf::from("/a"), PathBuf::from("/b")]);
+        assert_eq!(
+            config.lead_times,
+            [Duration::minutes(10), Duration::minutes(1)]
+        );
+        assert_eq!(config.sinks[0], SinkConfig::Stdout);
+        assert!(config.log_done);
+        assert!(config.apply_env(|_| Some(String::from("x"))).is_err());
+    }
+}
@@ -0,0 +1,163 @@
+//! Marking headings done from the command line, the way org does.
+//! A heading without repeater gets the first done keyword of its keyword sequence, and a
+//! "CLOSED:" timestamp when logging is enabled. A heading with a repeating scheduled date or
+//! deadline stays to do instead: its timestamps are shifted by their repeater, its keyword is
+//! reset to the first keyword of its sequence, ":LAST_REPEAT:" is set and a state note is added
+//! to its logbook.
+use crate::parsing::document::{Document, Heading};
+use crate::parsing::edit::{add_logbook_note, set_keyword, set_planning, set_property, Edit};
+use crate::parsing::keywords::KeywordState;
+use crate::parsing::timestamp::Timestamp;
+use chrono::NaiveDateTime;
+use std::error::Error;
+use std::fmt;
+
+/// Property holding the last time a repeating heading was done.
+pub const LAST_REPEAT_PROPERTY: &str = "LAST_REPEAT";
+/// Keyword used when the keyword sequence of a heading has no done keyword.
+const DEFAULT_DONE: &str = "DONE";
+
+/// Edits marking a heading done.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Done {
+    pub edits: Vec<Edit>,
+    /// Keyword of the heading once done: a done keyword, or an active one when it repeats
+    pub keyword: String,
+    /// Next occurrence of a repeating heading, i.e. its shifted scheduled date or deadline
+    pub next: Option<Timestamp>,
+}
+
+/// Error returned when a heading cannot be marked done.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DoneError {
+    /// The heading is not a task
+    NoKeyword,
+    AlreadyDone(String),
+}
+
+/// Mark `heading` done at `now`. `log_done` adds a "CLOSED:" timestamp to the headings
+/// without repeater.
+pub fn mark_done(
+    doc: &Document,
+    heading: &Heading,
+    now: NaiveDateTime,
+    log_done: bool,
+) -> Result<Done, DoneError> {
+    let keyword = heading.keyword.as_deref().ok_or(DoneError::NoKeyword)?;
+    if doc.keywords.state(keyword) == Some(KeywordState::Done) {
+        return Err(DoneError::AlreadyDone(String::from(keyword)));
+    }
+    let sequence = doc
+        .keywords
+        .sequences
+        .iter()
+        .find(|s| s.active.iter().any(|k| k.name == keyword));
+    let done_keyword = sequence
+        .and_then(|s| s.done.first())
+        .map_or(DEFAULT_DONE, |k| k.name.as_str());
+    let stamp = Timestamp::inactive(now);
+    let mut edits = vec![];
+    let mut next: Option<Timestamp> = None;
+    let planning = &heading.planning;
+    for (name, timestamp) in [
+        ("DEADLINE:", &planning.deadline),
+        ("SCHEDULED:", &planning.scheduled),
+    ] {
+        if let Some(shifted) = timestamp.as_ref().and_then(|t| t.repeat(now)) {
+            edits.push(set_planning(doc, heading, name, &shifted));
+            if next.as_ref().is_none_or(|n| shifted.start < n.start) {
+                next = Some(shifted);
+            }
+        }
+    }
+    if next.is_none() {
+        edits.push(set_keyword(doc, heading, done_keyword));
+        if log_done {
+            edits.push(set_planning(doc, heading, "CLOSED:", &stamp));
+        }
+        return Ok(Done {
+            edits,
+            keyword: String::from(done_keyword),
+            next,
+        });
+    }
+    let first = sequence
+        .and_then(|s| s.active.first())
+        .map_or(keyword, |k| k.name.as_str());
+    if first != keyword {
+        edits.push(set_keyword(doc, heading, first));
+    }
+    edits.push(set_property(
+        doc,
+        heading,
+        LAST_REPEAT_PROPERTY,
+        &stamp.to_string(),
+    ));
+    let note = format!(
+        "- State {:<12} from {:<12} {stamp}",
+        format!("\"{done_keyword}\""),
+        format!("\"{keyword}\"")
+    );
+    edits.push(add_logbook_note(doc, heading, &note));
+    Ok(Done {
+        edits,
+        keyword: String::from(first),
+        next,
+    })
+}
+
+impl fmt::Display for DoneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DoneError::NoKeyword => write!(f, "the heading has no TODO keyword"),
+            DoneError::AlreadyDone(keyword) => write!(f, "the heading is already {keyword}"),
+        }
+    }
+}
+
+impl Error for DoneError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{mark_done, DoneError};
+    use crate::parsing::document::Document;
+    use chrono::{NaiveDate, NaiveDateTime};
+    use std::path::Path;
+
+    fn now() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2023, 9, 6)
+            .unwrap()
+            .and_hms_opt(18, 30, 0)
+            .unwrap()
+    }
+
+    fn done(source: &str, log_done: bool) -> Result<String, DoneError> {
+        let doc = Document::parse(Path::new("a.org"), source.into(), &Default::default()).value;
+        let done = mark_done(&doc, &doc.headings[0], now(), log_done)?;
+        Ok(doc.apply(&done.edits))
+    }
+
+    #[test]
+    fn done_once() {
+        let source = "#+TODO: TODO NEXT | DONE CANCELLED\n* NEXT Deploy\n  DEADLINE: <2023-09-08 Fri>\nNotes\n";
+        assert_eq!(
+            done(source, true).unwrap(),
+            "#+TODO: TODO NEXT | DONE CANCELLED\n* DONE Deploy\n  CLOSED: [2023-09-06 Wed 18:30] DEADLINE: <2023-09-08 Fri>\nNotes\n"
+        );
+        assert_eq!(done("* TODO Deploy", false).unwrap(), "* DONE Deploy");
+        assert_eq!(
+            done("* DONE Deploy", true),
+            Err(DoneError::AlreadyDone("DONE".into()))
+        );
+        assert_eq!(done("* Deploy", true), Err(DoneError::NoKeyword));
+    }
+
+    #[test]
+    fn done_repeating() {
+        let source = "#+TODO: TODO NEXT | DONE\n* NEXT Water the plants\nSCHEDULED: <2023-09-04 Mon 08:00 ++1w>\n:PROPERTIES:\n:ID: plants\n:END:\n:LOGBOOK:\n- State \"DONE\"       from \"TODO\"       [2023-08-28 Mon 08:10]\n:END:\n";
+        assert_eq!(
+            done(source, true).unwrap(),
+            "#+TODO: TODO NEXT | DONE\n* TODO Water the plants\nSCHEDULED: <2023-09-11 Mon 08:00 ++1w>\n:PROPERTIES:\n:ID: plants\n:LAST_REPEAT: [2023-09-06 Wed 18:30]\n:END:\n:LOGBOOK:\n- State \"DONE\"       from \"NEXT\"       [2023-09-06 Wed 18:30]\n- State \"DONE\"       from \"TODO\"       [2023-08-28 Mon 08:10]\n:END:\n"
+        );
+    }
+}
@@ -0,0 +1,802 @@
+//! iCalendar (RFC 5545) export of the todos, to subscribe to them from calendar apps.
+//! Like org's own exporter, every todo gives a VTODO, starting at its scheduled date and due at
+//! its deadline, and every timestamp of a todo gives a VEVENT: scheduled dates are prefixed by
+//! "S: " and deadlines by "DL: ". Repeaters become RRULEs and warning delays become VALARMs.
+//! UIDs are the ":ID:" property of the todo when it has one, else a hash of its file and
+//! outline path, so that they stay the same from one export to the next.
+//! Calendars can also be read, to import their events: see `parse` and `Event`.
+use crate::parsing::file;
+use crate::parsing::timestamp::{Delay, Moment, Repeater, RepeaterKind, TimeUnit, Timestamp};
+use crate::parsing::Todo;
+use chrono::{
+    DateTime, Datelike, Days, Duration, FixedOffset, Local, NaiveDate, NaiveDateTime, NaiveTime,
+    TimeZone, Timelike, Utc,
+};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{self, Write};
+
+/// Property holding the unique identifier of a heading.
+pub const ID_PROPERTY: &str = "ID";
+/// Longest line of an iCalendar file, in bytes, line break excluded.
+const MAX_LINE: usize = 75;
+
+/// A calendar component, such as VEVENT, with its properties in order.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Component {
+    pub name: String,
+    /// Properties as (name with parameters, value)
+    pub properties: Vec<(String, String)>,
+    pub components: Vec<Component>,
+}
+
+impl Component {
+    pub fn new(name: &str) -> Component {
+        Component {
+            name: String::from(name),
+            ..Default::default()
+        }
+    }
+    /// Add a property. `value` must already be escaped if it is text.
+    pub fn push(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.properties.push((name.into(), value.into()));
+    }
+    /// Value of the first property named `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.find(name).map(|(_, value)| value)
+    }
+    /// Parameters and value of the first property named `name`.
+    /// Example: ("VALUE=DATE", "20230905") for "DTSTART;VALUE=DATE:20230905"
+    pub fn find(&self, name: &str) -> Option<(&str, &str)> {
+        self.properties.iter().find_map(|(n, value)| {
+            let parameters = match n.strip_prefix(name)? {
+                "" => "",
+                rest => rest.strip_prefix(';')?,
+            };
+            Some((parameters, value.as_str()))
+        })
+    }
+    /// Sub-components named `name`.
+    pub fn components<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Component> {
+        self.components.iter().filter(move |c| c.name == name)
+    }
+}
+
+impl fmt::Display for Component {
+    /// Write the component with CRLF line breaks, folding the lines longer than 75 bytes
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_line(f, &format!("BEGIN:{}", self.name))?;
+        for (name, value) in &self.properties {
+            write_line(f, &format!("{name}:{value}"))?;
+        }
+        for component in &self.components {
+            write!(f, "{component}")?;
+        }
+        write_line(f, &format!("END:{}", self.name))
+    }
+}
+
+/// Build the calendar of `todos`, named `name`. `stamp` is the current UTC time.
+pub fn calendar(todos: &[Todo], name: &str, stamp: NaiveDateTime) -> Component {
+    let mut calendar = Component::new("VCALENDAR");
+    calendar.push("VERSION", "2.0");
+    calendar.push(
+        "PRODID",
+        concat!(
+            "-//orgparser//orgparser ",
+            env!("CARGO_PKG_VERSION"),
+            "//EN"
+        ),
+    );
+    calendar.push("CALSCALE", "GREGORIAN");
+    calendar.push("X-WR-CALNAME", escape(name));
+    let stamp = stamp.format("%Y%m%dT%H%M%SZ").to_string();
+    for todo in todos {
+        let uid = uid(todo);
+        let events = [
+            ("SC", "S: ", todo.scheduled()),
+            ("DL", "DL: ", todo.deadline()),
+            ("TS", "", todo.timestamp()),
+        ];
+        for (prefix, summary, timestamp) in events {
+            if let Some(timestamp) = timestamp {
+                let mut event = Component::new("VEVENT");
+                event.push("UID", format!("{prefix}-{uid}"));
+                event.push("DTSTAMP", &stamp);
+                push_dates(&mut event, timestamp);
+                event.push("SUMMARY", escape(&format!("{summary}{}", todo.title())));
+                push_details(&mut event, todo, timestamp);
+                calendar.components.push(event);
+            }
+        }
+        let mut vtodo = Component::new("VTODO");
+        vtodo.push("UID", format!("TODO-{uid}"));
+        vtodo.push("DTSTAMP", &stamp);
+        let (start, due) = vtodo_moments(todo.scheduled(), todo.deadline());
+        if let Some(start) = start {
+            vtodo.push(date_property("DTSTART", &start), date_value(&start));
+        }
+        if let Some(due) = due {
+            vtodo.push(date_property("DUE", &due), date_value(&due));
+        }
+        vtodo.push("SUMMARY", escape(todo.title()));
+        vtodo.push("STATUS", "NEEDS-ACTION");
+        if let Some(priority) = todo.priority() {
+            vtodo.push("PRIORITY", priority_value(priority).to_string());
+        }
+        if let Some(timestamp) = todo.deadline().or(todo.scheduled()) {
+            push_details(&mut vtodo, todo, timestamp);
+        }
+        calendar.components.push(vtodo);
+    }
+    calendar
+}
+
+/// Start and due date of a VTODO, which must both be dates or both have a time: when only one
+/// has a time, the other is the start of its day for DTSTART or the end of its day for DUE.
+fn vtodo_moments(
+    scheduled: Option<&Timestamp>,
+    deadline: Option<&Timestamp>,
+) -> (Option<Moment>, Option<Moment>) {
+    let (mut start, mut due) = (scheduled.map(|t| t.start), deadline.map(|t| t.start));
+    if let (Some(start), Some(due)) = (&mut start, &mut due) {
+        match (start.time, due.time) {
+            (None, Some(_)) => start.time = Some(NaiveTime::MIN),
+            (Some(_), None) => due.time = NaiveTime::from_hms_opt(23, 59, 0),
+            _ => {}
+        }
+    }
+    (start, due)
+}
+
+/// Unique identifier of a todo: its ":ID:" property, else a hash of its file and outline path.
+pub fn uid(todo: &Todo) -> String {
+    if let Some(id) = todo.property(ID_PROPERTY) {
+        return String::from(id);
+    }
+    let content = format!("{}\n{}", todo.path().display(), todo.outline_path());
+    format!("{:016x}@orgparser", file::hash(content.as_bytes()))
+}
+
+/// Add DTSTART and DTEND of a timestamp. A day without time lasts until the next day.
+fn push_dates(component: &mut Component, timestamp: &Timestamp) {
+    let start = &timestamp.start;
+    component.push(date_property("DTSTART", start), date_value(start));
+    match (start.time, &timestamp.end) {
+        (Some(_), Some(end)) if end.time.is_some() => {
+            component.push("DTEND", date_value(end));
+        }
+        (Some(_), _) => {}
+        (None, end) => {
+            let last = end.map_or(start.date, |end| end.date);
+            let next = last.checked_add_days(Days::new(1)).unwrap_or(last);
+            component.push("DTEND;VALUE=DATE", format_date(next));
+        }
+    }
+}
+
+/// Add the repeater, the warning delay and the categories of a todo.
+fn push_details(component: &mut Component, todo: &Todo, timestamp: &Timestamp) {
+    if let Some(repeater) = &timestamp.repeater {
+        component.push("RRULE", rrule(repeater));
+    }
+    let categories: Vec<String> = std::iter::once(todo.category())
+        .filter(|c| !c.is_empty())
+        .chain(todo.all_tags())
+        .map(escape)
+        .collect();
+    if !categories.is_empty() {
+        component.push("CATEGORIES", categories.join(","));
+    }
+    if let Some(delay) = &timestamp.delay {
+        let mut alarm = Component::new("VALARM");
+        alarm.push("ACTION", "DISPLAY");
+        alarm.push("DESCRIPTION", escape(todo.title()));
+        alarm.push("TRIGGER", trigger(delay));
+        component.components.push(alarm);
+    }
+}
+
+/// Recurrence rule of a repeater. Example: "+2w" gives "FREQ=WEEKLY;INTERVAL=2"
+/// iCalendar has no equivalent of the "++" and ".+" repeaters, which repeat like "+".
+pub fn rrule(repeater: &Repeater) -> String {
+    let frequency = match repeater.unit {
+        TimeUnit::Hour => "HOURLY",
+        TimeUnit::Day => "DAILY",
+        TimeUnit::Week => "WEEKLY",
+        TimeUnit::Month => "MONTHLY",
+        TimeUnit::Year => "YEARLY",
+    };
+    format!("FREQ={frequency};INTERVAL={}", repeater.value.max(1))
+}
+
+/// Trigger of the alarm of a warning delay. Example: "-3d" gives "-P3D"
+pub fn trigger(delay: &Delay) -> String {
+    match delay.unit {
+        TimeUnit::Hour => format!("-PT{}H", delay.value),
+        TimeUnit::Day => format!("-P{}D", delay.value),
+        TimeUnit::Week => format!("-P{}W", delay.value),
+        TimeUnit::Month => format!("-P{}D", u64::from(delay.value) * 30),
+        TimeUnit::Year => format!("-P{}D", u64::from(delay.value) * 365),
+    }
+}
+
+/// Priority of a VTODO: 1 for "A" to 9, as iCalendar counts from the most important.
+fn priority_value(priority: char) -> u32 {
+    match priority.to_ascii_uppercase() {
+        c @ 'A'..='I' => u32::from(c) - u32::from('A') + 1,
+        c @ '0'..='9' => c.to_digit(10).unwrap_or(0).clamp(1, 9),
+        _ => 9,
+    }
+}
+
+/// Name of a date property: dates without time have the "VALUE=DATE" parameter.
+/// Dates with time are floating, i.e. in the local time of the calendar app, like in org.
+fn date_property(name: &str, moment: &Moment) -> String {
+    match moment.time {
+        Some(_) => String::from(name),
+        None => format!("{name};VALUE=DATE"),
+    }
+}
+
+fn date_value(moment: &Moment) -> String {
+    match moment.time {
+        Some(_) => moment.datetime().format("%Y%m%dT%H%M%S").to_string(),
+        None => format_date(moment.date),
+    }
+}
+
+fn format_date(date: NaiveDate) -> String {
+    date.format("%Y%m%d").to_string()
+}
+
+/// Escape a text value: backslashes, semicolons, commas and line breaks.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Write a content line, folded every 75 bytes without splitting a character.
+fn write_line(f: &mut fmt::Formatter, line: &str) -> fmt::Result {
+    let mut rest = line;
+    let mut limit = MAX_LINE;
+    while rest.len() > limit {
+        let mut split = limit;
+        while !rest.is_char_boundary(split) {
+            split -= 1;
+        }
+        f.write_str(&rest[..split])?;
+        f.write_str("\r\n ")?;
+        rest = &rest[split..];
+        // The leading space of a continuation line counts
+        limit = MAX_LINE - 1;
+    }
+    f.write_str(rest)?;
+    f.write_str("\r\n")
+}
+
+/// Write the calendar as the content of an .ics file.
+pub fn to_string(calendar: &Component) -> String {
+    let mut out = String::new();
+    let _ = write!(out, "{calendar}");
+    out
+}
+
+/// An event read from a calendar, as a timestamp with a title.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Event {
+    pub uid: String,
+    pub summary: String,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    /// Active timestamp of the event, with a repeater when its RRULE has an org equivalent
+    pub timestamp: Timestamp,
+    /// Recurrence rule of the event, as read
+    pub rrule: Option<String>,
+    /// Minutes between the first alarm and the start of the event
+    pub warntime: Option<i64>,
+    /// TZID of the start of the event when it is not a time zone of the calendar at a fixed
+    /// offset: its times are then read as local time
+    pub time_zone: Option<String>,
+}
+
+/// Offsets from UTC of the time zones of a calendar, by TZID.
+pub type TimeZones = HashMap<String, FixedOffset>;
+
+/// Error returned when a calendar or one of its events cannot be read.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum IcalError {
+    /// Invalid content line or unbalanced BEGIN and END, at a line of the file
+    Syntax { line: usize, reason: &'static str },
+    /// Event that cannot be imported, with its UID when it has one
+    Event {
+        uid: Option<String>,
+        reason: &'static str,
+    },
+}
+
+/// Read the first VCALENDAR of an iCalendar file.
+/// Folded lines are unfolded and property names are in uppercase. Values are not unescaped.
+pub fn parse(text: &str) -> Result<Component, IcalError> {
+    let mut lines: Vec<(usize, String)> = vec![];
+    for (index, line) in text.lines().enumerate() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(folded), Some((_, last))) => last.push_str(folded),
+            _ if line.is_empty() => {}
+            _ => lines.push((index + 1, String::from(line))),
+        }
+    }
+    let mut open: Vec<Component> = vec![];
+    for (line, content) in lines {
+        let syntax = |reason| IcalError::Syntax { line, reason };
+        let (name, value) = split_content_line(&content).ok_or(syntax("missing ':'"))?;
+        let upper = name.to_ascii_uppercase();
+        match upper.as_str() {
+            "BEGIN" => open.push(Component::new(&value.to_ascii_uppercase())),
+            "END" => {
+                let component = open.pop().ok_or(syntax("END without BEGIN"))?;
+                if !component.name.eq_ignore_ascii_case(value) {
+                    return Err(syntax("END does not match BEGIN"));
+                }
+                match open.last_mut() {
+                    Some(parent) => parent.components.push(component),
+                    None if component.name == "VCALENDAR" => return Ok(component),
+                    None => return Err(syntax("component outside of VCALENDAR")),
+                }
+            }
+            _ => {
+                let parent = open
+                    .last_mut()
+                    .ok_or(syntax("property outside of VCALENDAR"))?;
+                let name = match name.split_once(';') {
+                    Some((n, parameters)) => format!("{};{parameters}", n.to_ascii_uppercase()),
+                    None => upper,
+                };
+                parent.push(name, value);
+            }
+        }
+    }
+    Err(IcalError::Syntax {
+        line: text.lines().count(),
+        reason: "unclosed VCALENDAR",
+    })
+}
+
+/// Split a content line at the first colon outside of a quoted parameter value.
+fn split_content_line(line: &str) -> Option<(&str, &str)> {
+    let mut quoted = false;
+    for (index, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ':' if !quoted => return Some((&line[..index], &line[index + 1..])),
+            _ => {}
+        }
+    }
+    None
+}
+
+impl Event {
+    /// Read a VEVENT. Dates in UTC or in one of `zones` are converted to local time, and dates
+    /// with another TZID are read as local time. Modified occurrences of a recurring event,
+    /// which have a RECURRENCE-ID, cannot be imported as org has no equivalent.
+    pub fn from_component(event: &Component, zones: &TimeZones) -> Result<Event, IcalError> {
+        let uid = event.get("UID").map(unescape);
+        let error = |reason| IcalError::Event {
+            uid: uid.clone(),
+            reason,
+        };
+        if event.get("RECURRENCE-ID").is_some() {
+            return Err(error("modified occurrence of a recurring event"));
+        }
+        let (parameters, value) = event.find("DTSTART").ok_or(error("missing DTSTART"))?;
+        let start = parse_moment(parameters, value, zones).ok_or(error("invalid DTSTART"))?;
+        let time_zone = parameter(parameters, "TZID")
+            .filter(|tzid| !zones.contains_key(*tzid))
+            .map(String::from);
+        let end = match (event.find("DTEND"), event.get("DURATION")) {
+            (Some((p, v)), _) => Some(parse_moment(p, v, zones).ok_or(error("invalid DTEND"))?),
+            (None, Some(duration)) => {
+                let end = parse_duration(duration)
+                    .and_then(|duration| start.datetime().checked_add_signed(duration))
+                    .ok_or(error("invalid DURATION"))?;
+                Some(Moment {
+                    date: end.date(),
+                    time: start.time.map(|_| end.time()),
+                })
+            }
+            (None, None) => None,
+        };
+        let rrule = event.get("RRULE").map(String::from);
+        let timestamp = Timestamp {
+            active: true,
+            start,
+            end: end.and_then(|end| range_end(&start, end)),
+            repeater: rrule.as_deref().and_then(|r| repeater(r, start.date)),
+            delay: None,
+        };
+        let warntime = event.components("VALARM").find_map(|alarm| {
+            let (parameters, trigger) = alarm.find("TRIGGER")?;
+            if parameters.contains("VALUE=DATE-TIME") || parameters.contains("RELATED=END") {
+                return None;
+            }
+            Some(-parse_duration(trigger)?.num_minutes()).filter(|m| *m >= 0)
+        });
+        Ok(Event {
+            uid: uid.clone().ok_or(error("missing UID"))?,
+            summary: event.get("SUMMARY").map(unescape).unwrap_or_default(),
+            description: event.get("DESCRIPTION").map(unescape),
+            location: event.get("LOCATION").map(unescape),
+            timestamp,
+            rrule,
+            warntime,
+            time_zone,
+        })
+    }
+}
+
+/// Read the events of a calendar, with the errors of the events that cannot be read.
+pub fn events(calendar: &Component) -> (Vec<Event>, Vec<IcalError>) {
+    let zones = time_zones(calendar);
+    let mut events = vec![];
+    let mut errors = vec![];
+    for component in calendar.components("VEVENT") {
+        match Event::from_component(component, &zones) {
+            Ok(event) => events.push(event),
+            Err(e) => errors.push(e),
+        }
+    }
+    (events, errors)
+}
+
+/// Time zones of a calendar that are at a fixed offset, i.e. whose VTIMEZONE observances all
+/// have the same TZOFFSETTO. Zones with daylight saving time are left out.
+pub fn time_zones(calendar: &Component) -> TimeZones {
+    let mut zones = TimeZones::new();
+    for zone in calendar.components("VTIMEZONE") {
+        let offsets: Option<Vec<FixedOffset>> = zone
+            .components
+            .iter()
+            .map(|observance| parse_offset(observance.get("TZOFFSETTO")?))
+            .collect();
+        let (Some(tzid), Some(offsets)) = (zone.get("TZID"), offsets) else {
+            continue;
+        };
+        if let [offset, others @ ..] = offsets.as_slice() {
+            if others.iter().all(|o| o == offset) {
+                zones.insert(String::from(tzid), *offset);
+            }
+        }
+    }
+    zones
+}
+
+/// Read a UTC offset. Example: "+0100", "-0500" or "+053000"
+fn parse_offset(value: &str) -> Option<FixedOffset> {
+    let (sign, digits) = match value.split_at_checked(1)? {
+        ("+", digits) => (1, digits),
+        ("-", digits) => (-1, digits),
+        _ => return None,
+    };
+    if !matches!(digits.len(), 4 | 6) || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let field = |range: std::ops::Range<usize>| digits.get(range)?.parse::<i32>().ok();
+    let seconds = field(0..2)? * 3600 + field(2..4)? * 60 + field(4..6).unwrap_or(0);
+    FixedOffset::east_opt(sign * seconds)
+}
+
+/// Value of a parameter of a property, without quotes. Example: "Europe/Paris" for "TZID"
+/// in TZID="Europe/Paris";VALUE=DATE-TIME
+fn parameter<'a>(parameters: &'a str, name: &str) -> Option<&'a str> {
+    parameters.split(';').find_map(|p| {
+        let (n, value) = p.split_once('=')?;
+        n.eq_ignore_ascii_case(name)
+            .then(|| value.trim_matches('"'))
+    })
+}
+
+/// End of an org timestamp from the end of an event. DTEND is excluded for dates without time,
+/// so an event of a single day has no end.
+fn range_end(start: &Moment, end: Moment) -> Option<Moment> {
+    let end = match end.time {
+        Some(_) => end,
+        None => Moment {
+            date: end.date.checked_sub_days(Days::new(1))?,
+            time: None,
+        },
+    };
+    (end > *start).then_some(end)
+}
+
+/// Read a DATE or DATE-TIME value, converted to local time when it is in UTC or has the TZID of
+/// one of `zones`. Example: "20230905", "20230905T100000" or "20230905T080000Z"
+fn parse_moment(parameters: &str, value: &str, zones: &TimeZones) -> Option<Moment> {
+    if parameters.contains("VALUE=DATE") && !parameters.contains("VALUE=DATE-TIME")
+        || value.len() == 8
+    {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+        return Some(Moment { date, time: None });
+    }
+    let datetime = match value.strip_suffix('Z') {
+        Some(utc) => {
+            let utc = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+            DateTime::<Utc>::from_naive_utc_and_offset(utc, Utc)
+                .with_timezone(&Local)
+                .naive_local()
+        }
+        None => {
+            let datetime = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+            match parameter(parameters, "TZID").and_then(|tzid| zones.get(tzid)) {
+                Some(offset) => offset
+                    .from_local_datetime(&datetime)
+                    .single()?
+                    .with_timezone(&Local)
+                    .naive_local(),
+                None => datetime,
+            }
+        }
+    };
+    let time = datetime.time();
+    Some(Moment {
+        date: datetime.date(),
+        time: NaiveTime::from_hms_opt(time.hour(), time.minute(), 0),
+    })
+}
+
+/// Read a duration value. Example: "PT1H30M", "-P2D" or "P1W"
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let (sign, rest) = match value.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let rest = rest.strip_prefix('P')?;
+    let mut duration = Duration::zero();
+    let mut number = String::new();
+    let mut time = false;
+    for c in rest.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        if c == 'T' {
+            time = true;
+            continue;
+        }
+        let n: i64 = number.parse().ok()?;
+        number.clear();
+        let part = match (c, time) {
+            ('W', false) => Duration::try_weeks(n),
+            ('D', false) => Duration::try_days(n),
+            ('H', true) => Duration::try_hours(n),
+            ('M', true) => Duration::try_minutes(n),
+            ('S', true) => Duration::try_seconds(n),
+            _ => None,
+        };
+        duration = duration.checked_add(&part?)?;
+    }
+    number.is_empty().then_some(())?;
+    duration.checked_mul(sign)
+}
+
+/// Org repeater of a recurrence rule. Example: "FREQ=WEEKLY;INTERVAL=2" gives "+2w"
+/// Returns None for the rules org cannot repeat: with a COUNT or an UNTIL, or on other days
+/// than the day of `start`.
+pub fn repeater(rrule: &str, start: NaiveDate) -> Option<Repeater> {
+    let mut unit = None;
+    let mut value = 1;
+    for part in rrule.split(';') {
+        let (key, v) = part.split_once('=')?;
+        let v = v.to_ascii_uppercase();
+        let supported = match key.to_ascii_uppercase().as_str() {
+            "FREQ" => {
+                unit = Some(match v.as_str() {
+                    "HOURLY" => TimeUnit::Hour,
+                    "DAILY" => TimeUnit::Day,
+                    "WEEKLY" => TimeUnit::Week,
+                    "MONTHLY" => TimeUnit::Month,
+                    "YEARLY" => TimeUnit::Year,
+                    _ => return None,
+                });
+                true
+            }
+            "INTERVAL" => {
+                value = v.parse().ok().filter(|v| *v > 0)?;
+                true
+            }
+            "WKST" => true,
+            "BYDAY" => {
+                let weekday = start.weekday().to_string().to_ascii_uppercase();
+                weekday.starts_with(&v) && v.len() == 2
+            }
+            "BYMONTHDAY" => v.parse() == Ok(start.day()),
+            "BYMONTH" => v.parse() == Ok(start.month()),
+            _ => false,
+        };
+        if !supported {
+            return None;
+        }
+    }
+    Some(Repeater {
+        kind: RepeaterKind::Cumulate,
+        value,
+        unit: unit?,
+    })
+}
+
+/// Unescape a text value.
+pub fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => unescaped.push('\n'),
+            Some(c) => unescaped.push(c),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+impl fmt::Display for IcalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IcalError::Syntax { line, reason } => write!(f, "line {line}: {reason}"),
+            IcalError::Event {
+                uid: Some(uid),
+                reason,
+            } => write!(f, "event {uid}: {reason}"),
+            IcalError::Event { uid: None, reason } => write!(f, "event: {reason}"),
+        }
+    }
+}
+
+impl Error for IcalError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        calendar, escape, parse, parse_duration, repeater, to_string, trigger, uid, Event,
+    };
+    use crate::parsing::parse_file;
+    use crate::parsing::timestamp::{Delay, DelayKind, TimeUnit};
+    use chrono::{FixedOffset, Local, NaiveDate, TimeZone, Timelike};
+    use std::path::Path;
+
+    const FILE: &str = "* Work\n** TODO [#A] Weekly report, v2 :report:\nDEADLINE: <2023-09-08 Fri 17:00 +1w -2d>\n:PROPERTIES:\n:ID: 6f1c2a\n:END:\n** TODO Team offsite\nSCHEDULED: <2023-09-05 Tue>\n";
+
+    fn export() -> super::Component {
+        let todos = parse_file(Path::new("/org/work.org"), FILE, &Default::default()).value;
+        let stamp = NaiveDate::from_ymd_opt(2023, 9, 1)
+            .unwrap()
+            .and_hms_opt(8, 0, 0)
+            .unwrap();
+        calendar(&todos, "Work", stamp)
+    }
+
+    #[test]
+    fn events_and_todos() {
+        let calendar = export();
+        let names: Vec<&str> = calendar
+            .components
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(names, ["VEVENT", "VTODO", "VEVENT", "VTODO"]);
+        let deadline = &calendar.components[0];
+        assert_eq!(deadline.get("UID"), Some("DL-6f1c2a"));
+        assert_eq!(deadline.get("DTSTART"), Some("20230908T170000"));
+        assert_eq!(deadline.get("SUMMARY"), Some("DL: Weekly report\\, v2"));
+        assert_eq!(deadline.get("RRULE"), Some("FREQ=WEEKLY;INTERVAL=1"));
+        assert_eq!(deadline.get("CATEGORIES"), Some("work,report"));
+        assert_eq!(deadline.components[0].get("TRIGGER"), Some("-P2D"));
+        let vtodo = &calendar.components[1];
+        assert_eq!(vtodo.get("DUE"), Some("20230908T170000"));
+        assert_eq!(vtodo.get("PRIORITY"), Some("1"));
+        let offsite = &calendar.components[2];
+        assert_eq!(offsite.get("DTSTART;VALUE=DATE"), Some("20230905"));
+        assert_eq!(offsite.get("DTEND;VALUE=DATE"), Some("20230906"));
+        assert!(offsite.get("UID").unwrap().starts_with("SC-"));
+
+        // DTSTART and DUE of a VTODO have the same value type
+        let file = "* TODO Mixed\nSCHEDULED: <2023-09-05 Tue> DEADLINE: <2023-09-08 Fri 17:00>\n";
+        let todos = parse_file(Path::new("/org/a.org"), file, &Default::default()).value;
+        let stamp = NaiveDate::from_ymd_opt(2023, 9, 1)
+            .unwrap()
+            .and_hms_opt(8, 0, 0);
+        let mixed = super::calendar(&todos, "A", stamp.unwrap());
+        let vtodo = mixed.components.iter().find(|c| c.name == "VTODO").unwrap();
+        assert_eq!(vtodo.get("DTSTART"), Some("20230905T000000"));
+        assert_eq!(vtodo.get("DUE"), Some("20230908T170000"));
+        let long = Delay {
+            kind: DelayKind::All,
+            value: u32::MAX,
+            unit: TimeUnit::Year,
+        };
+        assert_eq!(trigger(&long), "-P1567663062675D");
+    }
+
+    #[test]
+    fn stable_uids() {
+        let todos = parse_file(Path::new("/org/work.org"), FILE, &Default::default()).value;
+        assert_eq!(uid(&todos[0]), "6f1c2a");
+        assert_eq!(uid(&todos[1]), "dc6aa41254818e15@orgparser");
+    }
+
+    #[test]
+    fn content_lines() {
+        let text = to_string(&export());
+        assert!(text.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(text.ends_with("END:VCALENDAR\r\n"));
+        assert!(text.lines().all(|l| l.len() <= 76));
+        assert_eq!(escape("a;b\\c\nd"), "a\\;b\\\\c\\nd");
+        let mut long = super::Component::new("VTODO");
+        long.push("SUMMARY", "é".repeat(50));
+        let text = long.to_string();
+        let lines: Vec<&str> = text.split("\r\n").collect();
+        assert_eq!(lines[1].len(), 74);
+        assert!(lines[2].starts_with(' '));
+        assert_eq!(lines[2].len(), 1 + 100 + 8 - 74);
+    }
+
+    #[test]
+    fn read_events() {
+        let calendar = parse("BEGIN:VCALENDAR\nBEGIN:VEVENT\nUID:a\nSUMMARY:Review\nDTSTART;TZID=\"Europe/Paris\":20230905T100000\nDURATION:PT1H30M\nRRULE:FREQ=MONTHLY;COUNT=3\nEND:VEVENT\nEND:VCALENDAR\n").unwrap();
+        let event = Event::from_component(&calendar.components[0], &Default::default()).unwrap();
+        assert_eq!(event.timestamp.to_string(), "<2023-09-05 Tue 10:00-11:30>");
+        assert_eq!(event.time_zone.as_deref(), Some("Europe/Paris"));
+        assert_eq!(event.rrule.as_deref(), Some("FREQ=MONTHLY;COUNT=3"));
+        let date = NaiveDate::from_ymd_opt(2023, 9, 5).unwrap();
+        let repeat = |rule| repeater(rule, date).map(|r| r.to_string());
+        assert_eq!(repeat("FREQ=DAILY;INTERVAL=3").as_deref(), Some("+3d"));
+        assert_eq!(repeat("FREQ=WEEKLY;BYDAY=TU").as_deref(), Some("+1w"));
+        assert_eq!(repeat("FREQ=WEEKLY;BYDAY=MO,TU"), None);
+        assert_eq!(repeat("FREQ=YEARLY;UNTIL=20250101"), None);
+        assert_eq!(
+            parse_duration("-P1DT2H").map(|d| d.num_minutes()),
+            Some(-1560)
+        );
+        assert_eq!(parse_duration("P99999999999999D"), None);
+        assert_eq!(parse_duration("P99999999999999999999W"), None);
+        assert_eq!(parse_duration("P100000000000DT200000000000H"), None);
+        let huge = parse("BEGIN:VCALENDAR\nBEGIN:VEVENT\nUID:a\nDTSTART:20230905T100000\nDURATION:P99999999D\nEND:VEVENT\nEND:VCALENDAR\n").unwrap();
+        assert!(Event::from_component(&huge.components[0], &Default::default()).is_err());
+        assert!(parse("BEGIN:VCALENDAR\nBEGIN:VEVENT\nEND:VCALENDAR\n").is_err());
+    }
+
+    #[test]
+    fn time_zones() {
+        let calendar = parse("BEGIN:VCALENDAR\nBEGIN:VTIMEZONE\nTZID:Bogota\nBEGIN:STANDARD\nDTSTART:19700101T000000\nTZOFFSETFROM:-0500\nTZOFFSETTO:-0500\nEND:STANDARD\nEND:VTIMEZONE\nBEGIN:VTIMEZONE\nTZID:Paris\nBEGIN:STANDARD\nTZOFFSETTO:+0100\nEND:STANDARD\nBEGIN:DAYLIGHT\nTZOFFSETTO:+0200\nEND:DAYLIGHT\nEND:VTIMEZONE\nBEGIN:VEVENT\nUID:a\nDTSTART;TZID=Bogota:20230905T100000\nDTEND;TZID=Bogota:20230905T110000\nEND:VEVENT\nBEGIN:VEVENT\nUID:b\nDTSTART;TZID=Paris:20230905T100000\nEND:VEVENT\nEND:VCALENDAR\n").unwrap();
+        let zones = super::time_zones(&calendar);
+        assert_eq!(zones.len(), 1);
+        let (events, errors) = super::events(&calendar);
+        assert!(errors.is_empty());
+        let bogota = FixedOffset::west_opt(5 * 3600).unwrap();
+        let local = |hour| {
+            let time = NaiveDate::from_ymd_opt(2023, 9, 5)
+                .unwrap()
+                .and_hms_opt(hour, 0, 0);
+            let time = bogota.from_local_datetime(&time.unwrap()).unwrap();
+            time.with_timezone(&Local).naive_local()
+        };
+        assert_eq!(events[0].timestamp.start.datetime(), local(10));
+        assert_eq!(events[0].timestamp.end.unwrap().datetime(), local(11));
+        assert_eq!(events[0].time_zone, None);
+        assert_eq!(events[1].timestamp.start.time.unwrap().hour(), 10);
+        assert_eq!(events[1].time_zone.as_deref(), Some("Paris"));
+    }
+}
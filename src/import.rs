@@ -0,0 +1,211 @@
+//! Import of calendar events into an org file.
+//! Every event becomes a heading with the first keyword of the file, scheduled at the
+//! timestamp of the event and with the UID of the event as ":ID:" property, so that it shows
+//! up in the agenda and fires reminders like any other todo. Importing a calendar again
+//! updates the headings having the UID of an event instead of adding new ones: their heading
+//! line, planning line and property drawer are rewritten, while their keyword, tags, notes and
+//! sub-headings are kept.
+use crate::ical::{Event, ID_PROPERTY};
+use crate::parsing::document::{Document, Heading};
+use crate::parsing::edit::{append_entry, Edit};
+use crate::parsing::heading::Heading as HeadingLine;
+use crate::parsing::planning::Planning;
+use crate::reminder::WARNTIME_PROPERTY;
+use std::error::Error;
+use std::fmt;
+
+/// Property holding the location of an event.
+pub const LOCATION_PROPERTY: &str = "LOCATION";
+
+/// Result of an import: the edits of the file and what changed.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Import {
+    pub edits: Vec<Edit>,
+    pub added: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+}
+
+/// Error returned when the events cannot be imported.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ImportError {
+    /// No heading has the outline path the events are imported under
+    HeadingNotFound(String),
+}
+
+/// Import `events` into `doc`. New headings are added at the end of the file, or as the last
+/// sub-headings of the heading with the outline path `parent`. Example: "Work / Meetings"
+/// Only the first event of a UID is imported.
+pub fn import(
+    doc: &Document,
+    parent: Option<&str>,
+    events: &[Event],
+) -> Result<Import, ImportError> {
+    let parent = match parent {
+        Some(path) => Some(
+            doc.find(path)
+                .ok_or_else(|| ImportError::HeadingNotFound(path.into()))?,
+        ),
+        None => None,
+    };
+    let newline = doc.newline();
+    let keyword = doc
+        .keywords
+        .sequences
+        .iter()
+        .flat_map(|s| &s.active)
+        .map(|k| k.name.clone())
+        .next();
+    let level = parent.map_or(1, |p| p.level + 1);
+    let mut import = Import::default();
+    let mut added = String::new();
+    let mut seen: Vec<&str> = vec![];
+    for event in events {
+        if seen.contains(&event.uid.as_str()) {
+            continue;
+        }
+        seen.push(&event.uid);
+        match doc
+            .iter()
+            .find(|h| h.property(ID_PROPERTY) == Some(&event.uid))
+        {
+            Some(heading) => {
+                let head = head(event, heading, newline);
+                if doc.text(&heading.head) == head {
+                    import.unchanged += 1;
+                } else {
+                    import.edits.push(Edit::replace(heading.head.clone(), head));
+                    import.updated += 1;
+                }
+            }
+            None => {
+                let heading = Heading {
+                    level,
+                    keyword: keyword.clone(),
+                    ..Default::default()
+                };
+                added.push_str(&head(event, &heading, newline));
+                for line in event.description.iter().flat_map(|d| d.lines()) {
+                    // A line starting with a star would be a heading
+                    if line.starts_with('*') {
+                        added.push(' ');
+                    }
+                    added.push_str(line);
+                    added.push_str(newline);
+                }
+                import.added += 1;
+            }
+        }
+    }
+    if let Some(added) = added.strip_suffix(newline) {
+        import.edits.push(append_entry(doc, parent, added));
+    }
+    Ok(import)
+}
+
+/// Heading line, planning line and property drawer of an event, keeping the level, keyword,
+/// priority, tags, other timestamps and other properties of `heading`.
+fn head(event: &Event, heading: &Heading, newline: &str) -> String {
+    let line = HeadingLine {
+        level: heading.level,
+        keyword: heading.keyword.clone(),
+        priority: heading.priority,
+        title: event.summary.lines().collect::<Vec<_>>().join(" "),
+        tags: heading.tags.clone(),
+    };
+    let planning = Planning {
+        scheduled: Some(event.timestamp.clone()),
+        ..heading.planning.clone()
+    };
+    let mut properties = heading.properties.clone();
+    set(&mut properties, ID_PROPERTY, Some(event.uid.clone()));
+    set(&mut properties, LOCATION_PROPERTY, event.location.clone());
+    set(
+        &mut properties,
+        WARNTIME_PROPERTY,
+        event.warntime.map(|m| m.to_string()),
+    );
+    let mut head = format!("{line}{newline}{planning}{newline}:PROPERTIES:{newline}");
+    for (key, value) in properties {
+        head.push_str(&format!(":{key}: {value}{newline}"));
+    }
+    head.push_str(":END:");
+    head.push_str(newline);
+    head
+}
+
+/// Set, replace or remove a property of a drawer. Property names are case-insensitive.
+fn set(properties: &mut Vec<(String, String)>, name: &str, value: Option<String>) {
+    let index = properties
+        .iter()
+        .position(|(key, _)| key.eq_ignore_ascii_case(name));
+    match (index, value) {
+        (Some(i), Some(value)) => properties[i].1 = value,
+        (Some(i), None) => {
+            properties.remove(i);
+        }
+        (None, Some(value)) => properties.push((String::from(name), value)),
+        (None, None) => {}
+    }
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImportError::HeadingNotFound(path) => write!(f, "no heading {path}"),
+        }
+    }
+}
+
+impl Error for ImportError {}
+
+#[cfg(test)]
+mod tests {
+    use super::import;
+    use crate::ical::{events, parse};
+    use crate::parsing::document::Document;
+    use std::path::Path;
+
+    const CALENDAR: &str = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VEVENT\r\nUID:standup@example.com\r\nSUMMARY:Stand-up\\, daily\r\nDTSTART:20230905T093000\r\nDTEND:20230905T094500\r\nRRULE:FREQ=WEEKLY;INTERVAL=1;BYDAY=TU\r\nLOCATION:Room 1\r\nDESCRIPTION:Agenda:\\n* blockers\r\nBEGIN:VALARM\r\nACTION:DISPLAY\r\nTRIGGER:-PT10M\r\nEND:VALARM\r\nEND:VEVENT\r\nBEGIN:VEVENT\r\nUID:offsite@example.com\r\nSUMMARY:Off\r\n site\r\nDTSTART;VALUE=DATE:20230911\r\nDTEND;VALUE=DATE:20230913\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+
+    fn parse_doc(source: &str) -> Document {
+        Document::parse(Path::new("cal.org"), source.into(), &Default::default()).value
+    }
+
+    #[test]
+    fn import_events() {
+        let (events, errors) = events(&parse(CALENDAR).unwrap());
+        assert!(errors.is_empty());
+        let doc = parse_doc("* Work\n** Meetings\n* Home");
+        let result = import(&doc, Some("Work / Meetings"), &events).unwrap();
+        assert_eq!(result.added, 2);
+        let source = doc.apply(&result.edits);
+        assert_eq!(
+            source,
+            "* Work\n** Meetings\n*** TODO Stand-up, daily\nSCHEDULED: <2023-09-05 Tue 09:30-09:45 +1w>\n:PROPERTIES:\n:ID: standup@example.com\n:LOCATION: Room 1\n:APPT_WARNTIME: 10\n:END:\nAgenda:\n * blockers\n*** TODO Offsite\nSCHEDULED: <2023-09-11 Mon>--<2023-09-12 Tue>\n:PROPERTIES:\n:ID: offsite@example.com\n:END:\n* Home"
+        );
+        let todos = parse_doc(&source).todos();
+        assert_eq!(todos.len(), 2);
+        assert_eq!(todos[0].outline_path(), "Work / Meetings / Stand-up, daily");
+        assert!(import(&doc, Some("Meetings"), &events).is_err());
+    }
+
+    #[test]
+    fn idempotent_updates() {
+        let (events, _) = events(&parse(CALENDAR).unwrap());
+        let empty = parse_doc("");
+        let first = empty.apply(&import(&empty, None, &events).unwrap().edits);
+        let edited = first
+            .replace("TODO Stand-up, daily", "DONE Stand-up :work:")
+            .replace("Agenda:", "My notes");
+        let doc = parse_doc(&edited);
+        let second = import(&doc, None, &events).unwrap();
+        assert_eq!((second.added, second.updated, second.unchanged), (0, 1, 1));
+        let doc = parse_doc(&doc.apply(&second.edits));
+        assert!(doc.source.contains("* DONE Stand-up, daily :work:\n"));
+        assert!(doc.source.contains("My notes"));
+        let third = import(&doc, None, &events).unwrap();
+        assert_eq!((third.added, third.updated, third.unchanged), (0, 0, 2));
+        assert!(third.edits.is_empty());
+    }
+}
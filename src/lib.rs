@@ -0,0 +1,19 @@
+// Simple utility to automate the process of reminder for emacs orgmode.
+// This library holds the parsing logic and the reminder daemon used by the orgparser binary.
+
+pub mod agenda;
+pub mod archive;
+pub mod cache;
+pub mod capture;
+pub mod config;
+pub mod done;
+pub mod ical;
+pub mod import;
+pub mod output;
+pub mod parsing;
+pub mod reminder;
+pub mod report;
+pub mod schedule;
+pub mod select;
+pub mod sink;
+pub mod watch;
@@ -2,190 +2,685 @@
 // This application parse the org mode file(s) and look for the next scheduled event/todo
 // It generates a notification n minutes before the event takes place.
 
-use parsing::generate_todos;
-
-/// Module to iterate through the org directory and find .org files.
-/// It ignores hidden directories (directories startign with ".")
-mod parsing {
-    use chrono::{NaiveDateTime, ParseError};
-    use rayon::prelude::*;
-    use std::fmt;
-    use std::path::PathBuf;
-    use tokio::fs::read_to_string;
-    use walkdir::{DirEntry, WalkDir};
-
-    /// Return the list of .org files in the org directory
-    fn get_org_entries(org_dir: &str) -> Vec<PathBuf> {
-        let walker = WalkDir::new(org_dir);
-        // walker.into_iter().filter_entry( wj)
-        walker
-            .into_iter()
-            .filter_entry(is_org_file)
-            .map(|r| r.unwrap())
-            .map(|de| de.path().to_path_buf())
-            .collect()
-    }
-    /// Verify if a single DirEntry is an org file.
-    /// It verifies if a DirEntry is both a file and if it is, if it's extension is ".org"
-    fn is_org_file(entry: &DirEntry) -> bool {
-        if entry.metadata().unwrap().is_file() {
-            return entry
-                .file_name()
-                .to_str()
-                .map(|s| s.ends_with(".org"))
-                .unwrap_or(false);
-        }
-        false
-    }
-    /// Returns the content of the org files inside the org directory as a Vector of String.
-    async fn read_org_files(org_dir: &str) -> Vec<String> {
-        let org_entries = get_org_entries(org_dir);
-        let mut string_files: Vec<String> = vec![];
-        for entry in org_entries {
-            if let Ok(file_string) = read_to_string(entry).await {
-                string_files.push(file_string)
+use chrono::{Datelike, Duration, NaiveDate};
+use clap::Parser;
+use cli::{Cli, Command};
+use orgparser::agenda::{Agenda, Span};
+use orgparser::archive::{self, Moved};
+use orgparser::cache::{self, Cache};
+use orgparser::capture::{self, Context};
+use orgparser::config::Config;
+use orgparser::done;
+use orgparser::ical;
+use orgparser::import;
+use orgparser::output::{self, Format};
+use orgparser::parsing;
+use orgparser::parsing::document::{Document, Heading};
+use orgparser::parsing::edit::Edit;
+use orgparser::parsing::file::{self, LockPolicy, Origin};
+use orgparser::reminder;
+use orgparser::report::{ClockGroup, ClockReport};
+use orgparser::schedule::{self, DateInput, PlanningKind};
+use orgparser::select::Selector;
+use orgparser::sink::{MultiSink, NotificationSink};
+use orgparser::watch::{self, TodoIndex};
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use tokio::sync::mpsc;
+
+mod cli;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let mut config = match Config::load(cli.config.as_deref()) {
+        Ok(config) => config,
+        Err(e) => return error(e),
+    };
+    if !cli.org_dirs.is_empty() {
+        config.org_roots = cli.org_dirs;
+    }
+    let mode = WriteMode {
+        dry_run: cli.dry_run,
+        lock: match cli.force {
+            true => LockPolicy::Ignore,
+            false => LockPolicy::Refuse,
+        },
+    };
+    let default = Command::List {
+        tags: vec![],
+        format: Format::Text,
+    };
+    match cli.command.unwrap_or(default) {
+        Command::List { tags, format } => list(config, &tags, format).await,
+        Command::Agenda { span, start } => agenda(config, span, start).await,
+        Command::Daemon {
+            sinks,
+            lead_minutes,
+        } => {
+            if !sinks.is_empty() {
+                config.sinks = sinks;
             }
-        }
-        string_files
-    }
-    /// Generate the TodoVec for a given file converted into a String.
-    fn iterate_over_file(file: String) -> TodoVec {
-        let todo_list: TodoVec = file
-            .as_str()
-            .par_lines()
-            .filter(|l| Todo::filter(l))
-            .map(Todo::parse_todo)
-            .filter(|t| t.is_some())
-            .map(|t| t.unwrap())
-            .collect();
-        todo_list
-    }
-    /// Generate all the todos for a fiven org_directory
-    /// This function is the entry point for parsing the org directory and the org files
-    pub async fn generate_todos(org_dir: &str) -> TodoVec {
-        let files_content = read_org_files(org_dir).await;
-        let todo_vec: Vec<Vec<Todo>> = files_content
-            .into_par_iter()
-            .map(iterate_over_file)
-            .collect();
-        todo_vec.into_iter().flatten().collect::<Vec<Todo>>() // Flatten the vector of vector into a TodoVec
-    }
-
-    /// The struct holding reference to a single todo.
-    /// Its role is to parse a given line into an easy to manipulate todo item
-    #[derive(Clone, Eq, PartialEq)]
-    pub struct Todo {
-        item: String,
-        date: NaiveDateTime,
-    }
-
-    /// Our Todo list.
-    /// A file has a single (possibly empty) TodoList
-    /// We have as manu TodoVec objects as we have org files inside the org directory
-    type TodoVec = Vec<Todo>;
-
-    /// Verify if line contains a 'TODO' item and date and if so, generate a single Todo for a given line
-    impl Todo {
-        pub fn parse_todo(line: &str) -> Option<Todo> {
-            let item: Vec<&str> = line.split("*TODO").collect();
-            let item = String::from(item[1]); // Select the second item, following the "*TOD O"
-            match Self::parse_date(line) {
-                Ok(datetime) => Some(Todo {
-                    item,
-                    date: datetime,
-                }),
-                Err(_) => None,
+            if !lead_minutes.is_empty() {
+                config.lead_times = lead_minutes
+                    .into_iter()
+                    .map(|m| Duration::minutes(m.into()))
+                    .collect();
             }
+            daemon(config).await
         }
-        /// Verify if a line contains "*TODO" and ("DEALINE" or "SCHEDULE")
-        pub fn filter(line: &str) -> bool {
-            line.contains("*TODO") && (line.contains("DEADLINE") || line.contains("SCHEDULED"))
-        }
-        /// Find the date inside of a line (&str)
-        //BUG: problem when there is another '<' inside the T O D O object
-        //BUG: problem when there is a ' ' (blank space) before the date. Example: < 2023-05-18 ...>
-        // This will print an error in case of failure, but won't panic otherwise
-        fn parse_date(line: &str) -> Result<NaiveDateTime, ParseError> {
-            let parse_from_str = NaiveDateTime::parse_from_str;
-            let date_str = Self::find_date(line);
-            let formated_with_date_and_time = parse_from_str(date_str, "%Y-%m-%d %a %H:%M"); // Formater. Example: 2023-09-05 Tue 10:06
-            let formated_with_date = parse_from_str(date_str, "%Y-%m-%d %a"); // Formater. Example: 2023-09-05 Tue
-            let formated_with_time = parse_from_str(date_str, "%Y-%m-%d %H:%M"); // Formater. Example: 2023-09-05 10:06
-            let formated = parse_from_str(date_str, "%Y-%m-%d"); // Formater. Example: 2023-09-05
-                                                                 // These conditionals will verify if the date is parsed for at leat one of the 3 parsers above
-                                                                 // If not, it will return an error
-            if formated_with_date_and_time.is_ok() {
-                formated
-            } else if formated_with_date.is_ok() {
-                formated_with_date
-            } else if formated_with_time.is_ok() {
-                formated_with_time
-            } else if formated.is_ok() {
-                formated
-            } else {
-                println!("error:{}", formated_with_date_and_time.unwrap_err());
-                formated_with_date_and_time
+        Command::Check => check(config).await,
+        Command::ClockReport { from, to, group } => clock_report(config, from, to, group).await,
+        Command::ExportIcs { output, name } => export_ics(config, mode, &output, &name).await,
+        Command::ImportIcs {
+            calendar,
+            file,
+            heading,
+        } => import_ics(config, mode, &calendar, &file, heading.as_deref()),
+        Command::Done { selector } => mark_done(config, mode, &selector).await,
+        Command::Schedule {
+            selector,
+            date,
+            log,
+        } => reschedule(config, mode, &selector, PlanningKind::Scheduled, &date, log).await,
+        Command::Deadline {
+            selector,
+            date,
+            log,
+        } => reschedule(config, mode, &selector, PlanningKind::Deadline, &date, log).await,
+        Command::Refile {
+            selector,
+            file,
+            heading,
+        } => refile(config, mode, &selector, file.as_deref(), heading.as_deref()).await,
+        Command::Archive {
+            selector,
+            done_before_days,
+            location,
+        } => {
+            if let Some(location) = location {
+                config.archive_location = location;
+            }
+            match (selector, done_before_days) {
+                (Some(selector), _) => archive_heading(config, mode, &selector).await,
+                (None, days) => archive_done(config, mode, days.unwrap_or_default()).await,
             }
         }
-        fn find_date(line: &str) -> &str {
-            let date_split: Vec<&str> = line.split('<').collect();
-            let right_of_date = date_split[1]; // right side of "<"
-            let date_str_split: Vec<&str> = right_of_date.split('>').collect();
-            date_str_split[0] // left side of " " (blank space)
-        }
-    }
-    impl fmt::Display for Todo {
-        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-            let item = &self.item;
-            let date = &self.date;
-            write!(f, "{item},{date}")
-        }
-    }
-    mod tests {
-        #[test]
-        fn test_finding_date() {
-            let line0 = "*TODO this should be good <2023-08-08>"; //NOTE: Must change Sun to another day
-            let line1 = "*TODO this should be good <2023-08-08 Sun 10:10>"; //NOTE: Must change Sun to another day
-            let line2 = "*TODO this should be good too <2023-08-08 10:10>";
-            assert_eq!(super::Todo::find_date(line0), "2023-08-08");
-            assert_eq!(super::Todo::find_date(line1), "2023-08-08 Sun 10:10");
-            assert_eq!(super::Todo::find_date(line2), "2023-08-08 10:10")
-        }
-
-        #[test]
-        fn testing_parse_todo() {
-            let line0 = "*TODO this should be good <2023-08-08>"; //NOTE: Must change Sun to another day
-            let line1 = "*TODO this should be good <2023-08-08 Sun 10:10>";
-            let line2 = "*TODO this should be good too <2023-08-08 10:10>";
-            let line3 = "*TODO this should be no gucci <Sun 10:10>";
-            let line4 = "**TODO this should be no gucci <10:10>";
-            let good_lines = vec![line0, line1, line2];
-            let bad_lines = vec![line3, line4];
-            for lines in good_lines {
-                let x = super::Todo::parse_date(lines);
-                assert!(x.is_ok())
+        Command::Capture {
+            template,
+            link,
+            fields,
+            text,
+        } => capture(config, mode, &template, link, &fields, text.join(" ")).await,
+    }
+}
+
+/// How the commands write their files
+#[derive(Clone, Copy, Debug)]
+struct WriteMode {
+    /// Print a diff instead of writing
+    dry_run: bool,
+    lock: LockPolicy,
+}
+
+/// Print an error and return a failure exit code
+fn error(e: impl std::fmt::Display) -> ExitCode {
+    eprintln!("error:{e}");
+    ExitCode::FAILURE
+}
+
+/// Parse every org file of the configuration
+async fn build_index(config: &Config) -> TodoIndex {
+    TodoIndex::build(
+        config.org_roots.clone(),
+        config.exclude.clone(),
+        config.todo_keywords.clone(),
+    )
+    .await
+}
+
+/// Index the todos of every org file of the configuration, parsing only the files that changed
+/// since the last run when the cache is enabled. The index has no document of the other files.
+async fn index_todos(config: &Config) -> TodoIndex {
+    if config.cache && !cfg!(feature = "cache") {
+        eprintln!(
+            "warning:the cache is enabled, but orgparser was built without the \"cache\" feature"
+        );
+    }
+    let cache = match cache::default_path() {
+        Some(path) if config.cache => Cache::load(path, &config.todo_keywords),
+        _ => Cache::disabled(),
+    };
+    let mut index = TodoIndex::build_with_cache(
+        config.org_roots.clone(),
+        config.exclude.clone(),
+        config.todo_keywords.clone(),
+        cache,
+    )
+    .await;
+    if let Err(e) = index.save_cache() {
+        eprintln!("warning:{e}");
+    }
+    index
+}
+
+/// Print the errors of the index on the standard error
+fn report_errors(index: &TodoIndex) -> usize {
+    let mut count = 0;
+    for e in index.errors() {
+        eprintln!("warning:{e}");
+        count += 1;
+    }
+    count
+}
+
+/// Read and parse an org file, a missing file being empty
+fn read_org_file(config: &Config, path: &Path) -> Result<Document, String> {
+    let (source, origin) = match file::read(path) {
+        Ok(read) => read,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            (String::new(), Origin::new(None, ""))
+        }
+        Err(e) => return Err(format!("{}: {e}", path.display())),
+    };
+    let mut doc = Document::parse(path, source, &config.todo_keywords).value;
+    doc.origin = Some(origin);
+    Ok(doc)
+}
+
+/// Write `doc` with `edits` applied, or print the diff of the edits in a dry run
+fn save(mode: WriteMode, doc: &Document, edits: &[Edit]) -> Result<(), String> {
+    let edited = doc.edit(edits).value;
+    if mode.dry_run {
+        print!(
+            "{}",
+            file::unified_diff(&doc.path, &doc.source, &edited.source)
+        );
+        return Ok(());
+    }
+    if mode.lock == LockPolicy::Ignore {
+        if let Some(owner) = file::lock_owner(&doc.path) {
+            let path = doc.path.display();
+            eprintln!("warning:{path} has unsaved changes in emacs ({owner})");
+        }
+    }
+    edited.save_with(mode.lock).map_err(|e| e.to_string())
+}
+
+/// Whether two paths are the same file
+fn same_file(a: &Path, b: &Path) -> bool {
+    a == b || matches!((a.canonicalize(), b.canonicalize()), (Ok(a), Ok(b)) if a == b)
+}
+
+/// Write the edits of moved subtrees. Both files are checked before either is written, and the
+/// target is written first, so that a failure never loses the subtrees
+fn save_moved(
+    mode: WriteMode,
+    source: &Document,
+    target: &Document,
+    moved: Moved,
+) -> Result<(), String> {
+    if source.path == target.path {
+        let edits: Vec<Edit> = moved.source.into_iter().chain(moved.target).collect();
+        return save(mode, source, &edits);
+    }
+    if !mode.dry_run {
+        for doc in [target, source] {
+            doc.check_writable(mode.lock).map_err(|e| e.to_string())?;
+        }
+    }
+    save(mode, target, &moved.target)?;
+    save(mode, source, &moved.source)
+}
+
+/// Print the todos having every tag of `tags` in `format`, sorted by date
+async fn list(config: Config, tags: &[String], format: Format) -> ExitCode {
+    let index = index_todos(&config).await;
+    report_errors(&index);
+    let mut todo_vec = index.into_todos();
+    todo_vec.retain(|t| tags.iter().all(|tag| t.has_tag(tag)));
+    todo_vec.sort_by_key(|t| t.date());
+    match output::write_todos(&todo_vec, format, std::io::stdout().lock()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => error(e),
+    }
+}
+
+/// Print the agenda of `span`, from `start` if given
+async fn agenda(config: Config, span: Span, start: Option<NaiveDate>) -> ExitCode {
+    let now = reminder::now();
+    let (first, days) = match start {
+        Some(start) => (start, span.days()),
+        None => span.range(now.date()),
+    };
+    let index = index_todos(&config).await;
+    report_errors(&index);
+    print!("{}", Agenda::new(&index.into_todos(), first, days, now));
+    ExitCode::SUCCESS
+}
+
+/// Fire the reminders until the process is killed, re-reading the files that change
+async fn daemon(config: Config) -> ExitCode {
+    let mut sink = match MultiSink::from_configs(&config.sinks) {
+        Ok(sink) => sink,
+        Err(e) => return error(e),
+    };
+    let index = index_todos(&config).await;
+    report_errors(&index);
+    let todo_vec = index.todos();
+    let (sender, receiver) = mpsc::channel(1);
+    let watcher = tokio::spawn(watch::watch(index, sender));
+    reminder::run(todo_vec, config.lead_times, receiver, |r| {
+        if let Err(e) = sink.notify(r) {
+            eprintln!("error:{e}");
+        }
+    })
+    .await;
+    match watcher.await {
+        Ok(Err(e)) => error(e),
+        _ => ExitCode::SUCCESS,
+    }
+}
+
+/// Verify that the configuration can be used and print what was found
+async fn check(config: Config) -> ExitCode {
+    let mut status = ExitCode::SUCCESS;
+    if let Err(e) = MultiSink::from_configs(&config.sinks) {
+        status = error(e);
+    }
+    for root in config.org_roots.iter().filter(|r| !r.is_dir()) {
+        status = error(format!("{} is not a directory", root.display()));
+    }
+    let index = index_todos(&config).await;
+    let errors = report_errors(&index);
+    println!(
+        "{} org files, {} todos, {} errors",
+        index.len(),
+        index.todos().len(),
+        errors
+    );
+    if errors > 0 {
+        status = ExitCode::FAILURE;
+    }
+    status
+}
+
+/// Print the time clocked between the start of `from` and the end of `to`
+async fn clock_report(
+    config: Config,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    group: ClockGroup,
+) -> ExitCode {
+    let now = reminder::now();
+    let today = now.date();
+    let from = from.unwrap_or(today.with_day(1).unwrap_or(today));
+    let to = to.unwrap_or(today);
+    if to < from {
+        return error(format!("the range ends before it starts: {from} to {to}"));
+    }
+    let index = build_index(&config).await;
+    report_errors(&index);
+    let report = ClockReport::new(
+        index.documents(),
+        group,
+        from.and_time(Default::default()),
+        to.and_time(Default::default()) + Duration::days(1),
+        now,
+    );
+    println!("{report}");
+    ExitCode::SUCCESS
+}
+
+/// Write the todos having a date to the iCalendar file `output`
+async fn export_ics(config: Config, mode: WriteMode, output: &Path, name: &str) -> ExitCode {
+    let index = index_todos(&config).await;
+    report_errors(&index);
+    let mut todo_vec = index.into_todos();
+    todo_vec.sort_by_key(|t| t.date());
+    let calendar = ical::calendar(&todo_vec, name, chrono::Utc::now().naive_utc());
+    let text = ical::to_string(&calendar);
+    if output == Path::new("-") {
+        print!("{text}");
+        return ExitCode::SUCCESS;
+    }
+    if mode.dry_run {
+        let old = std::fs::read_to_string(output).unwrap_or_default();
+        print!("{}", file::unified_diff(output, &old, &text));
+        return ExitCode::SUCCESS;
+    }
+    match file::write(output, &text, None, mode.lock) {
+        Ok(()) => {
+            println!("{} todos exported to {}", todo_vec.len(), output.display());
+            ExitCode::SUCCESS
+        }
+        Err(e) => error(e),
+    }
+}
+
+/// Import the events of the iCalendar file `calendar` into the org file `file`, under `heading`
+fn import_ics(
+    config: Config,
+    mode: WriteMode,
+    calendar: &Path,
+    file: &Path,
+    heading: Option<&str>,
+) -> ExitCode {
+    let calendar = match std::fs::read_to_string(calendar) {
+        Ok(text) => ical::parse(&text).map_err(|e| format!("{}: {e}", calendar.display())),
+        Err(e) => Err(format!("{}: {e}", calendar.display())),
+    };
+    let (events, errors) = match calendar {
+        Ok(calendar) => ical::events(&calendar),
+        Err(e) => return error(e),
+    };
+    for e in errors {
+        eprintln!("warning:{e}");
+    }
+    for event in &events {
+        if let Some(tzid) = &event.time_zone {
+            eprintln!(
+                "warning:event {}: time zone {tzid} is not at a fixed offset, its time is read as local time",
+                event.uid
+            );
+        }
+    }
+    for event in events.iter().filter(|e| e.timestamp.repeater.is_none()) {
+        if let Some(rrule) = &event.rrule {
+            eprintln!(
+                "warning:event {}: no org repeater for {rrule}, only the first occurrence is imported",
+                event.uid
+            );
+        }
+    }
+    let doc = match read_org_file(&config, file) {
+        Ok(doc) => doc,
+        Err(e) => return error(e),
+    };
+    let result = match import::import(&doc, heading, &events) {
+        Ok(result) => result,
+        Err(e) => return error(format!("{}: {e}", file.display())),
+    };
+    if !result.edits.is_empty() {
+        if let Err(e) = save(mode, &doc, &result.edits) {
+            return error(e);
+        }
+    }
+    println!(
+        "{} added, {} updated, {} unchanged",
+        result.added, result.updated, result.unchanged
+    );
+    ExitCode::SUCCESS
+}
+
+/// Mark the heading matching `selector` done, and write its file
+async fn mark_done(config: Config, mode: WriteMode, selector: &Selector) -> ExitCode {
+    let index = build_index(&config).await;
+    report_errors(&index);
+    let (doc, heading) = match selector.find(index.documents()) {
+        Ok(found) => found,
+        Err(e) => return error(e),
+    };
+    let location = format!("{}:{}", doc.path.display(), heading.line);
+    let done = match done::mark_done(doc, heading, reminder::now(), config.log_done) {
+        Ok(done) => done,
+        Err(e) => return error(format!("{location}: {e}")),
+    };
+    if let Err(e) = save(mode, doc, &done.edits) {
+        return error(e);
+    }
+    match done.next {
+        Some(next) => println!(
+            "{location}: {} {}, next on {next}",
+            done.keyword, heading.title
+        ),
+        None => println!("{location}: {} {}", done.keyword, heading.title),
+    }
+    ExitCode::SUCCESS
+}
+
+/// Set the scheduled date or the deadline of the heading matching `selector`, and write its file
+async fn reschedule(
+    config: Config,
+    mode: WriteMode,
+    selector: &Selector,
+    kind: PlanningKind,
+    date: &DateInput,
+    log: bool,
+) -> ExitCode {
+    let index = build_index(&config).await;
+    report_errors(&index);
+    let (doc, heading) = match selector.find(index.documents()) {
+        Ok(found) => found,
+        Err(e) => return error(e),
+    };
+    let location = format!("{}:{}", doc.path.display(), heading.line);
+    let log = log || config.log_reschedule;
+    let rescheduled = match schedule::reschedule(doc, heading, kind, date, reminder::now(), log) {
+        Ok(rescheduled) => rescheduled,
+        Err(e) => return error(format!("{location}: {e}")),
+    };
+    if let Err(e) = save(mode, doc, &rescheduled.edits) {
+        return error(e);
+    }
+    println!(
+        "{location}: {} {} {}",
+        heading.title,
+        kind.keyword(),
+        rescheduled.timestamp
+    );
+    ExitCode::SUCCESS
+}
+
+/// Move the heading matching `selector` under the heading `heading` of `file`
+async fn refile(
+    config: Config,
+    mode: WriteMode,
+    selector: &Selector,
+    file: Option<&Path>,
+    heading: Option<&str>,
+) -> ExitCode {
+    let index = build_index(&config).await;
+    report_errors(&index);
+    let (doc, found) = match selector.find(index.documents()) {
+        Ok(found) => found,
+        Err(e) => return error(e),
+    };
+    let read;
+    let target = match file.filter(|file| !same_file(file, &doc.path)) {
+        Some(file) => match read_org_file(&config, file) {
+            Ok(target) => {
+                read = target;
+                &read
             }
-            for lines in bad_lines {
-                let x = super::Todo::parse_date(lines);
-                assert!(x.is_err())
+            Err(e) => return error(e),
+        },
+        None => doc,
+    };
+    let moved = match archive::refile(doc, &[found], target, heading) {
+        Ok(moved) => moved,
+        Err(e) => return error(format!("{}:{}: {e}", doc.path.display(), found.line)),
+    };
+    if let Err(e) = save_moved(mode, doc, target, moved) {
+        return error(e);
+    }
+    println!(
+        "{}:{}: {} refiled to {}",
+        doc.path.display(),
+        found.line,
+        found.title,
+        target.path.display()
+    );
+    ExitCode::SUCCESS
+}
+
+/// Archive the heading matching `selector` to the archive location of the configuration
+async fn archive_heading(config: Config, mode: WriteMode, selector: &Selector) -> ExitCode {
+    let index = build_index(&config).await;
+    report_errors(&index);
+    let (doc, found) = match selector.find(index.documents()) {
+        Ok(found) => found,
+        Err(e) => return error(e),
+    };
+    match archive_headings(&config, mode, doc, &[found]) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => error(e),
+    }
+}
+
+/// Archive the headings of every org file marked done more than `days` days ago
+async fn archive_done(config: Config, mode: WriteMode, days: u32) -> ExitCode {
+    let index = build_index(&config).await;
+    report_errors(&index);
+    let before = reminder::now() - Duration::days(days.into());
+    let location = &config.archive_location;
+    let paths: Vec<PathBuf> = index.documents().map(|doc| doc.path.clone()).collect();
+    let mut status = ExitCode::SUCCESS;
+    for path in paths {
+        let same = same_file(&location.file_for(&path), &path);
+        if same && location.heading.is_none() {
+            continue;
+        }
+        // Read again, as an earlier file may have been archived to this one
+        let doc = match read_org_file(&config, &path) {
+            Ok(doc) => doc,
+            Err(e) => {
+                status = error(e);
+                continue;
             }
+        };
+        let mut headings = archive::done_before(&doc, before);
+        let archive = location.heading.as_deref().and_then(|h| doc.find(h));
+        if let Some(archive) = archive.filter(|_| same) {
+            headings.retain(|h| !archive.span.contains(&h.span.start));
+        }
+        if headings.is_empty() {
+            continue;
+        }
+        if let Err(e) = archive_headings(&config, mode, &doc, &headings) {
+            status = error(e);
         }
     }
+    status
 }
-#[tokio::main]
-async fn main() {
-    let org_dir = "/home/simon/org"; // Should be absolute path!
-    let todo_vec = generate_todos(org_dir).await;
-    println!("{}", todo_vec.len() ); //BUG: length of vector is 0
-    for todo in todo_vec {
-        println!("{todo}");
+
+/// Archive `headings` of `doc` to the archive location of the configuration, and print them
+fn archive_headings(
+    config: &Config,
+    mode: WriteMode,
+    doc: &Document,
+    headings: &[&Heading],
+) -> Result<(), String> {
+    let location = &config.archive_location;
+    let path = location.file_for(&doc.path);
+    let read;
+    let target = match same_file(&path, &doc.path) {
+        true => doc,
+        false => {
+            read = read_org_file(config, &path)?;
+            &read
+        }
+    };
+    let parent = location.heading.as_deref();
+    let moved = archive::archive(doc, headings, target, parent, reminder::now())
+        .map_err(|e| format!("{}: {e}", doc.path.display()))?;
+    save_moved(mode, doc, target, moved)?;
+    for heading in headings {
+        println!(
+            "{}:{}: {} archived to {}",
+            doc.path.display(),
+            heading.line,
+            heading.title,
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Add an entry from the capture template `key`, and read its file again like the daemon does
+async fn capture(
+    config: Config,
+    mode: WriteMode,
+    key: &str,
+    link: Option<String>,
+    fields: &[(String, String)],
+    text: String,
+) -> ExitCode {
+    let Some(template) = config.templates.get(key) else {
+        let keys: Vec<&str> = config.templates.keys().map(String::as_str).collect();
+        return error(format!(
+            "no template {key}, expected one of {}",
+            keys.join(", ")
+        ));
+    };
+    let file = match (template.file.is_relative(), config.org_roots.first()) {
+        (true, Some(root)) => root.join(&template.file),
+        _ => template.file.clone(),
+    };
+    let context = Context {
+        now: reminder::now(),
+        text,
+        link,
+    };
+    let interactive = std::io::stdin().is_terminal();
+    let entry = template.expand(&context, |name, default| {
+        if let Some((_, value)) = fields.iter().find(|(n, _)| n == name) {
+            return Some(value.clone());
+        }
+        if !interactive {
+            return None;
+        }
+        match default {
+            Some(default) => eprint!("{name} [{default}]: "),
+            None => eprint!("{name}: "),
+        }
+        let _ = std::io::stderr().flush();
+        let mut line = String::new();
+        std::io::stdin().lock().read_line(&mut line).ok()?;
+        let line = line.trim_end_matches(['\n', '\r']);
+        (!line.is_empty()).then(|| String::from(line))
+    });
+    let entry = match entry {
+        Ok(entry) => entry,
+        Err(e) => return error(format!("template {key}: {e}")),
+    };
+    let doc = match read_org_file(&config, &file) {
+        Ok(doc) => doc,
+        Err(e) => return error(e),
+    };
+    let edit = match capture::capture(&doc, template, &entry) {
+        Ok(edit) => edit,
+        Err(e) => return error(format!("{}: {e}", file.display())),
+    };
+    let line = doc.source[..edit.range.start].lines().count() + 1;
+    if let Err(e) = save(mode, &doc, &[edit]) {
+        return error(e);
+    }
+    if mode.dry_run {
+        return ExitCode::SUCCESS;
+    }
+    if !config.org_roots.iter().any(|root| file.starts_with(root)) {
+        eprintln!(
+            "warning:{} is not under an org root, the daemon will not see it",
+            file.display()
+        );
     }
+    // The file is read again the way the daemon reads the files that change
+    match parsing::read_document(&file, &config.todo_keywords).await {
+        Ok(parsed) => match parsed.value.iter().find(|h| h.line == line) {
+            Some(heading) => println!("{}:{}: {}", file.display(), heading.line, heading.title),
+            None => return error(format!("{}: the entry cannot be read back", file.display())),
+        },
+        Err(e) => return error(e),
+    }
+    ExitCode::SUCCESS
 }
 
 #[cfg(test)]
 mod tests {
-    use super::parsing;
     use core::iter::zip;
+    use orgparser::parsing;
     #[test]
     fn filtering_lines() {
         let lines = vec![
@@ -193,8 +688,9 @@ mod tests {
             "*TODO: ranger ma chambre DEADLINE: <blabla>",
             "*TODO: ranger ma chambre SCHEDULED: <blabla>",
             " ranger ma chambre SCHEDULED: <blabla>",
+            "* TODO ranger ma chambre",
         ];
-        let right_answers = vec![false, true, true, false];
+        let right_answers = vec![false, true, true, false, true];
         for (line, answer) in zip(lines, right_answers) {
             assert_eq!(parsing::Todo::filter(line), answer);
         }
@@ -218,4 +714,12 @@ mod tests {
             assert!(x.is_none(), "value of parsing: {}:", x.unwrap())
         }
     }
+    #[test]
+    fn testing_parse_entry() {
+        let heading = "* TODO Write report";
+        let planning = "  SCHEDULED: <2023-09-05 Tue 10:00>";
+        let todo = parsing::Todo::parse_entry(heading, Some(planning));
+        assert!(todo.is_some());
+        assert!(parsing::Todo::parse_entry(heading, None).is_none());
+    }
 }
@@ -0,0 +1,182 @@
+//! Output formats of the todo list: "title,date" lines, JSON or NDJSON.
+//! JSON writes a single array, NDJSON writes one object per line. Both need the "json"
+//! feature, and every object holds the file path, line, outline path, keyword, priority, tags,
+//! timestamps and properties of a todo.
+use crate::parsing::Todo;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+/// Output format of the todo list.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Format {
+    /// One "title,date" line per todo
+    #[default]
+    Text,
+    Json,
+    Ndjson,
+}
+
+/// Error returned when a format cannot be used.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FormatError {
+    Unknown(String),
+    /// JSON output is not compiled in
+    JsonUnsupported,
+}
+
+#[cfg(feature = "json")]
+mod record {
+    use crate::parsing::properties::Properties;
+    use crate::parsing::timestamp::Timestamp;
+    use crate::parsing::Todo;
+    use chrono::NaiveDateTime;
+    use serde::Serialize;
+    use std::path::Path;
+
+    /// A todo as written in JSON.
+    #[derive(Serialize)]
+    pub struct Record<'a> {
+        path: &'a Path,
+        line: usize,
+        outline: &'a [String],
+        outline_path: String,
+        category: &'a str,
+        keyword: &'a str,
+        priority: Option<char>,
+        title: &'a str,
+        tags: &'a [String],
+        inherited_tags: &'a [String],
+        date: NaiveDateTime,
+        scheduled: Option<&'a Timestamp>,
+        deadline: Option<&'a Timestamp>,
+        closed: Option<&'a Timestamp>,
+        timestamp: Option<&'a Timestamp>,
+        properties: &'a Properties,
+    }
+
+    impl<'a> From<&'a Todo> for Record<'a> {
+        fn from(todo: &'a Todo) -> Self {
+            Record {
+                path: todo.path(),
+                line: todo.line(),
+                outline: todo.outline(),
+                outline_path: todo.outline_path(),
+                category: todo.category(),
+                keyword: todo.keyword(),
+                priority: todo.priority(),
+                title: todo.title(),
+                tags: todo.tags(),
+                inherited_tags: todo.inherited_tags(),
+                date: todo.date(),
+                scheduled: todo.scheduled(),
+                deadline: todo.deadline(),
+                closed: todo.closed(),
+                timestamp: todo.timestamp(),
+                properties: todo.properties(),
+            }
+        }
+    }
+}
+
+/// Write the todos to `out` in `format`.
+pub fn write_todos<W: Write>(todos: &[Todo], format: Format, mut out: W) -> io::Result<()> {
+    match format {
+        Format::Text => {
+            for todo in todos {
+                writeln!(out, "{todo}")?;
+            }
+        }
+        #[cfg(feature = "json")]
+        Format::Json => {
+            let records: Vec<record::Record> = todos.iter().map(record::Record::from).collect();
+            serde_json::to_writer(&mut out, &records)?;
+            writeln!(out)?;
+        }
+        #[cfg(feature = "json")]
+        Format::Ndjson => {
+            for todo in todos {
+                serde_json::to_writer(&mut out, &record::Record::from(todo))?;
+                writeln!(out)?;
+            }
+        }
+        #[cfg(not(feature = "json"))]
+        Format::Json | Format::Ndjson => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                FormatError::JsonUnsupported,
+            ))
+        }
+    }
+    out.flush()
+}
+
+impl FromStr for Format {
+    type Err = FormatError;
+    /// Parse a format as "text", "json" or "ndjson"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let format = match s.trim() {
+            "text" => return Ok(Format::Text),
+            "json" => Format::Json,
+            "ndjson" => Format::Ndjson,
+            other => return Err(FormatError::Unknown(String::from(other))),
+        };
+        if cfg!(feature = "json") {
+            Ok(format)
+        } else {
+            Err(FormatError::JsonUnsupported)
+        }
+    }
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FormatError::Unknown(name) => {
+                write!(f, "unknown format: {name} (expected text, json or ndjson)")
+            }
+            FormatError::JsonUnsupported => write!(f, "JSON output needs the \"json\" feature"),
+        }
+    }
+}
+
+impl Error for FormatError {}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use super::{write_todos, Format};
+    use crate::parsing::parse_file;
+    use std::path::Path;
+
+    #[test]
+    fn json_output() {
+        let file = "* Projects :work:\n** TODO [#A] Deploy, then celebrate :ops:\nSCHEDULED: <2023-09-05 Tue 10:00 +1w>\n:PROPERTIES:\n:ID: deploy\n:END:\n";
+        let todos = parse_file(Path::new("/org/work.org"), file, &Default::default()).value;
+        let mut out = vec![];
+        write_todos(&todos, Format::Ndjson, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.lines().count(), 1);
+        let value: serde_json::Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(value["path"], "/org/work.org");
+        assert_eq!(value["line"], 2);
+        assert_eq!(value["outline_path"], "Projects / Deploy, then celebrate");
+        assert_eq!(value["priority"], "A");
+        assert_eq!(value["tags"], serde_json::json!(["ops"]));
+        assert_eq!(value["inherited_tags"], serde_json::json!(["work"]));
+        assert_eq!(value["date"], "2023-09-05T10:00:00");
+        assert_eq!(value["scheduled"]["repeater"]["unit"], "week");
+        assert_eq!(value["properties"]["ID"], "deploy");
+
+        let mut out = vec![];
+        write_todos(&todos, Format::Json, &mut out).unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(value.as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn formats() {
+        assert_eq!("ndjson".parse(), Ok(Format::Ndjson));
+        assert!("csv".parse::<Format>().is_err());
+    }
+}
@@ -0,0 +1,635 @@
+//! Module to iterate through the org directory and find .org files.
+//! It ignores hidden directories (directories startign with ".")
+use chrono::NaiveDateTime;
+use rayon::prelude::*;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use walkdir::{DirEntry, WalkDir};
+
+pub mod document;
+pub mod edit;
+pub mod error;
+pub mod file;
+pub mod heading;
+pub mod keywords;
+pub mod logbook;
+pub mod planning;
+pub mod properties;
+pub mod tags;
+pub mod timestamp;
+
+use document::{Document, Inherited};
+use error::{ParseError, Parsed};
+use file::Origin;
+use heading::Heading;
+use keywords::{KeywordState, TodoKeywords};
+use planning::{Planning, PLANNING_KEYWORDS};
+use properties::{Properties, CATEGORY};
+use timestamp::Timestamp;
+
+/// Return the list of .org files in the org directory
+/// Hidden directories and files are skipped, but the org directory itself may be hidden.
+/// Directories that cannot be walked are reported as errors.
+pub fn get_org_entries(org_dir: impl AsRef<Path>) -> Parsed<Vec<PathBuf>> {
+    let org_dir = org_dir.as_ref();
+    let walker = WalkDir::new(org_dir);
+    let mut entries: Parsed<Vec<PathBuf>> = Parsed::default();
+    for entry in walker
+        .into_iter()
+        .filter_entry(|de| de.depth() == 0 || !is_hidden(de))
+    {
+        match entry.map_err(|e| walk_error(org_dir, e)).and_then(|de| {
+            let is_org = is_org_file(&de)?;
+            Ok(Some(de).filter(|_| is_org))
+        }) {
+            Ok(Some(de)) => entries.value.push(de.into_path()),
+            Ok(None) => {}
+            Err(e) => entries.errors.push(e),
+        }
+    }
+    entries
+}
+fn walk_error(org_dir: &Path, source: walkdir::Error) -> ParseError {
+    let path = source.path().unwrap_or(org_dir).to_path_buf();
+    ParseError::Walk { path, source }
+}
+/// Verify if a single DirEntry is hidden, i.e. if its name starts with "."
+fn is_hidden(entry: &DirEntry) -> bool {
+    entry
+        .file_name()
+        .to_str()
+        .map(|s| s.starts_with('.'))
+        .unwrap_or(false)
+}
+/// Verify if a single DirEntry is an org file.
+/// It verifies if a DirEntry is both a file and if it is, if it's extension is ".org"
+/// Symlinks are followed, and a broken symlink is an error.
+fn is_org_file(entry: &DirEntry) -> Result<bool, ParseError> {
+    if !is_org_path(entry.path()) {
+        return Ok(false);
+    }
+    if !entry.file_type().is_symlink() {
+        return Ok(entry.file_type().is_file());
+    }
+    std::fs::metadata(entry.path())
+        .map(|metadata| metadata.is_file())
+        .map_err(|source| ParseError::Read {
+            path: entry.path().to_path_buf(),
+            source,
+        })
+}
+/// Verify if a path is the one of an org file: its extension is ".org", and it is neither an
+/// emacs lock file (".#file.org") nor an auto-save file ("#file.org#").
+pub fn is_org_path(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.ends_with(".org") && !name.starts_with(".#") && !name.starts_with('#'))
+        .unwrap_or(false)
+}
+/// Returns the path, the content and the state of the org files inside the org directory.
+async fn read_org_files(org_dir: &Path) -> Parsed<Vec<(PathBuf, String, Origin)>> {
+    let Parsed {
+        value: org_entries,
+        mut errors,
+    } = get_org_entries(org_dir);
+    let mut string_files = vec![];
+    for entry in org_entries {
+        match file::read_async(&entry).await {
+            Ok((file_string, origin)) => string_files.push((entry, file_string, origin)),
+            Err(source) => errors.push(ParseError::Read {
+                path: entry,
+                source,
+            }),
+        }
+    }
+    Parsed {
+        value: string_files,
+        errors,
+    }
+}
+/// Read and parse a single org file.
+/// Returns an error if the file cannot be read at all.
+pub async fn read_document(
+    path: &Path,
+    keywords: &TodoKeywords,
+) -> Result<Parsed<Document>, ParseError> {
+    let (file, origin) = file::read_async(path)
+        .await
+        .map_err(|source| ParseError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+    let mut parsed = Document::parse(path, file, keywords);
+    parsed.value.origin = Some(origin);
+    Ok(parsed)
+}
+/// Generate the TodoVec of a single org file.
+/// Returns an error if the file cannot be read at all.
+pub async fn generate_file_todos(
+    path: &Path,
+    keywords: &TodoKeywords,
+) -> Result<Parsed<TodoVec>, ParseError> {
+    Ok(read_document(path, keywords).await?.map(|doc| doc.todos()))
+}
+/// Generate the TodoVec for a given file converted into a String.
+/// `keywords` is used when the file does not define its own "#+TODO:" sequences.
+pub fn parse_file(path: &Path, file: &str, keywords: &TodoKeywords) -> Parsed<TodoVec> {
+    Document::parse(path, String::from(file), keywords).map(|doc| doc.todos())
+}
+/// Parse every org file of a given org_directory, using `keywords` as the user-wide default
+/// keyword sequences.
+/// Files that cannot be read and invalid planning lines are reported as errors.
+pub async fn parse_documents(
+    org_dir: impl AsRef<Path>,
+    keywords: &TodoKeywords,
+) -> Parsed<Vec<Document>> {
+    let files_content = read_org_files(org_dir.as_ref()).await;
+    let parsed: Vec<Parsed<Document>> = files_content
+        .value
+        .into_par_iter()
+        .map(|(path, file, origin)| {
+            let mut parsed = Document::parse(&path, file, keywords);
+            parsed.value.origin = Some(origin);
+            parsed
+        })
+        .collect();
+    let mut errors = files_content.errors;
+    let mut documents = vec![];
+    for doc in parsed {
+        documents.push(doc.value);
+        errors.extend(doc.errors);
+    }
+    Parsed {
+        value: documents,
+        errors,
+    }
+}
+/// Generate all the todos for a fiven org_directory
+/// This function is the entry point for parsing the org directory and the org files
+pub async fn generate_todos(org_dir: impl AsRef<Path>) -> Parsed<TodoVec> {
+    generate_todos_with_keywords(org_dir, &TodoKeywords::default()).await
+}
+/// Generate all the todos for a given org_directory, using `keywords` as the user-wide
+/// default keyword sequences.
+/// Files that cannot be read and invalid planning lines are reported as errors.
+pub async fn generate_todos_with_keywords(
+    org_dir: impl AsRef<Path>,
+    keywords: &TodoKeywords,
+) -> Parsed<TodoVec> {
+    parse_documents(org_dir, keywords)
+        .await
+        .map(|docs| docs.iter().flat_map(Document::todos).collect())
+}
+
+/// A heading line and the planning line that belongs to it, if any.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct HeadingLines<'a> {
+    /// Line number of the heading, starting at 1
+    pub line: usize,
+    pub heading: &'a str,
+    pub planning: Option<&'a str>,
+}
+
+/// Iterate over the headings of a file.
+/// In org, the planning line (SCHEDULED, DEADLINE, CLOSED) must directly follow its heading.
+/// Any other line below the heading is section content and is skipped.
+pub fn headings(file: &str) -> impl Iterator<Item = HeadingLines<'_>> {
+    let mut lines = file.lines().enumerate().peekable();
+    std::iter::from_fn(move || {
+        let (index, heading) = lines.by_ref().find(|(_, l)| is_heading(l))?;
+        let planning = lines.next_if(|(_, l)| is_planning(l)).map(|(_, l)| l);
+        Some(HeadingLines {
+            line: index + 1,
+            heading,
+            planning,
+        })
+    })
+}
+/// Verify if a line is a heading, i.e. it starts with one or more stars followed by a blank space.
+/// The star must be at the very beginning of the line, otherwise it is a list item or bold text.
+/// "*TODO" without a blank space, e.g. "*TODO: title", is still accepted for backward
+/// compatibility, but only as a whole word: "*TODOs*" is bold text.
+pub fn is_heading(line: &str) -> bool {
+    let rest = line.trim_start_matches('*');
+    let legacy = rest
+        .strip_prefix("TODO")
+        .is_some_and(|r| r.is_empty() || r.starts_with([':', ' ', '\t']));
+    rest.len() < line.len() && (rest.is_empty() || rest.starts_with(char::is_whitespace) || legacy)
+}
+/// Verify if a line is a planning line, i.e. it starts with "SCHEDULED:", "DEADLINE:" or "CLOSED:"
+pub fn is_planning(line: &str) -> bool {
+    let line = line.trim_start();
+    PLANNING_KEYWORDS.iter().any(|k| line.starts_with(k))
+}
+
+/// The struct holding reference to a single todo.
+/// Its role is to parse a given heading into an easy to manipulate todo item
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Todo {
+    /// Path of the org file of the todo, empty when it was not parsed from a file
+    path: PathBuf,
+    /// Line number of the heading, starting at 1, or 0 when it was not parsed from a file
+    line: usize,
+    level: usize,
+    keyword: String,
+    priority: Option<char>,
+    title: String,
+    tags: Vec<String>,
+    scheduled: Option<Timestamp>,
+    deadline: Option<Timestamp>,
+    closed: Option<Timestamp>,
+    timestamp: Option<Timestamp>,
+    /// Titles of the parent headings, from the top level one
+    outline: Vec<String>,
+    /// Properties of the drawer of the heading
+    properties: Properties,
+    /// Properties of the file and of the drawers of the parents, overridden by the drawer of
+    /// the heading
+    inherited_properties: Properties,
+    /// Tags of the file and of the parent headings, without the tags of the heading
+    inherited_tags: Vec<String>,
+    /// Group tags matching the tags of the todo, from the "#+TAGS:" groups of its file
+    group_tags: Vec<String>,
+    /// Category of the todo, shown in the agenda
+    category: String,
+}
+
+/// Our Todo list.
+/// A file has a single (possibly empty) TodoList
+/// We have as manu TodoVec objects as we have org files inside the org directory
+pub type TodoVec = Vec<Todo>;
+
+/// Verify if line contains a 'TODO' item and date and if so, generate a single Todo for a given line
+impl Todo {
+    pub fn parse_todo(line: &str) -> Option<Todo> {
+        Self::parse_entry(line, None)
+    }
+    /// Generate a single Todo from a heading and its planning line.
+    /// When there is no planning line, the planning is looked up on the heading line itself.
+    pub fn parse_entry(heading: &str, planning: Option<&str>) -> Option<Todo> {
+        Self::parse_entry_with_keywords(heading, planning, &TodoKeywords::default())
+    }
+    /// Generate a single Todo from a heading and its planning line.
+    /// Only headings with an active keyword (e.g. TODO, NEXT) are todos.
+    pub fn parse_entry_with_keywords(
+        heading_line: &str,
+        planning: Option<&str>,
+        keywords: &TodoKeywords,
+    ) -> Option<Todo> {
+        let heading = Heading::parse_with_keywords(heading_line, &keywords.names())?;
+        let planning = Planning::parse(planning.unwrap_or(heading_line));
+        Self::from_parts(heading, planning, keywords)
+    }
+    /// Generate a single Todo from a heading of `doc`, with what it inherits from the file and
+    /// from its parents.
+    pub fn from_heading(
+        heading: &document::Heading,
+        inherited: &Inherited,
+        doc: &Document,
+    ) -> Option<Todo> {
+        let headline = Heading {
+            level: heading.level,
+            keyword: heading.keyword.clone(),
+            priority: heading.priority,
+            title: heading.title.clone(),
+            tags: heading.tags.clone(),
+        };
+        let mut todo = Self::from_parts(headline, heading.planning.clone(), &doc.keywords)?;
+        todo.path = doc.path.clone();
+        todo.line = heading.line;
+        todo.outline = inherited.outline.clone();
+        todo.properties.extend(&heading.properties);
+        todo.inherited_properties = inherited.properties.clone();
+        todo.inherited_properties.extend(&heading.properties);
+        todo.category = match todo.inherited_property(CATEGORY) {
+            Some(category) => String::from(category),
+            None => doc
+                .path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        };
+        todo.inherited_tags = inherited
+            .tags
+            .iter()
+            .filter(|t| !todo.tags.contains(t))
+            .cloned()
+            .collect();
+        let mut group_tags = vec![];
+        for tag in todo.all_tags() {
+            for group in doc.tag_groups.group_tags(tag) {
+                if !group_tags.contains(&group) {
+                    group_tags.push(group);
+                }
+            }
+        }
+        todo.group_tags = group_tags.into_iter().map(String::from).collect();
+        Some(todo)
+    }
+    /// Generate a single Todo from a parsed heading and planning.
+    fn from_parts(heading: Heading, planning: Planning, keywords: &TodoKeywords) -> Option<Todo> {
+        let Heading {
+            level,
+            keyword,
+            priority,
+            title,
+            tags,
+        } = heading;
+        let keyword = keyword.filter(|k| keywords.state(k) == Some(KeywordState::Active))?;
+        let Planning {
+            scheduled,
+            deadline,
+            closed,
+        } = planning;
+        let timestamp = Timestamp::find_active(&title);
+        if scheduled.is_none() && deadline.is_none() && timestamp.is_none() {
+            return None;
+        }
+        Some(Todo {
+            path: PathBuf::new(),
+            line: 0,
+            level,
+            keyword,
+            priority,
+            title,
+            tags,
+            scheduled,
+            deadline,
+            closed,
+            timestamp,
+            outline: vec![],
+            properties: Properties::default(),
+            inherited_properties: Properties::default(),
+            inherited_tags: vec![],
+            group_tags: vec![],
+            category: String::new(),
+        })
+    }
+    /// Verify if a line is a heading with an active keyword, using the default keywords
+    pub fn filter(line: &str) -> bool {
+        let keywords = TodoKeywords::default();
+        Heading::parse_with_keywords(line, &keywords.names())
+            .and_then(|h| h.keyword)
+            .is_some_and(|k| keywords.state(&k) == Some(KeywordState::Active))
+    }
+    /// Path of the org file of the todo
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    /// Line number of the heading of the todo, starting at 1
+    pub fn line(&self) -> usize {
+        self.line
+    }
+    /// Outline level of the todo, i.e. the number of stars of its heading
+    pub fn level(&self) -> usize {
+        self.level
+    }
+    /// TODO keyword of the heading
+    pub fn keyword(&self) -> &str {
+        &self.keyword
+    }
+    /// Priority cookie of the heading. Example: 'A' for "[#A]"
+    pub fn priority(&self) -> Option<char> {
+        self.priority
+    }
+    /// Title of the heading, without keyword, priority and tags
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+    /// Tags of the heading
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+    /// Tags inherited from "#+FILETAGS:" and from the parent headings
+    pub fn inherited_tags(&self) -> &[String] {
+        &self.inherited_tags
+    }
+    /// Tags of the heading, then inherited tags
+    pub fn all_tags(&self) -> Vec<&str> {
+        self.tags
+            .iter()
+            .chain(&self.inherited_tags)
+            .map(String::as_str)
+            .collect()
+    }
+    /// Verify if the todo has a tag, directly, by inheritance, or through a tag group whose
+    /// group tag is `tag`
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags
+            .iter()
+            .chain(&self.inherited_tags)
+            .chain(&self.group_tags)
+            .any(|t| t == tag)
+    }
+    /// Titles of the parent headings, from the top level one
+    pub fn outline(&self) -> &[String] {
+        &self.outline
+    }
+    /// Outline path of the todo, its own title included. Example: "Projects / Website / Deploy"
+    pub fn outline_path(&self) -> String {
+        let mut path = self.outline.clone();
+        path.push(self.title.clone());
+        path.join(" / ")
+    }
+    /// Category of the todo: its inherited CATEGORY property, else the "#+CATEGORY:" of its
+    /// file, else the name of its file without extension
+    pub fn category(&self) -> &str {
+        &self.category
+    }
+    /// Properties of the drawer of the heading
+    pub fn properties(&self) -> &Properties {
+        &self.properties
+    }
+    /// Value of a property of the drawer of the heading
+    pub fn property(&self, name: &str) -> Option<&str> {
+        self.properties.get(name)
+    }
+    /// Value of a property of the heading, else of its closest parent defining it, else of the
+    /// file, like org does for inherited properties
+    pub fn inherited_property(&self, name: &str) -> Option<&str> {
+        self.inherited_properties.get(name)
+    }
+    /// Values allowed for a property, as listed by the inherited "NAME_ALL" property
+    pub fn allowed_values(&self, name: &str) -> Vec<&str> {
+        self.inherited_properties.allowed_values(name)
+    }
+    /// SCHEDULED timestamp of the planning line
+    pub fn scheduled(&self) -> Option<&Timestamp> {
+        self.scheduled.as_ref()
+    }
+    /// DEADLINE timestamp of the planning line
+    pub fn deadline(&self) -> Option<&Timestamp> {
+        self.deadline.as_ref()
+    }
+    /// CLOSED timestamp of the planning line
+    pub fn closed(&self) -> Option<&Timestamp> {
+        self.closed.as_ref()
+    }
+    /// First active timestamp found in the title of the heading
+    pub fn timestamp(&self) -> Option<&Timestamp> {
+        self.timestamp.as_ref()
+    }
+    /// Timestamp used for the date of the todo: the scheduled date, else the deadline, else
+    /// the timestamp of the title
+    fn main_timestamp(&self) -> &Timestamp {
+        self.scheduled
+            .iter()
+            .chain(&self.deadline)
+            .chain(&self.timestamp)
+            .next()
+            .expect("a todo has at least one timestamp")
+    }
+    /// Date of the todo: the scheduled date, else the deadline, else the timestamp of the title
+    pub fn date(&self) -> NaiveDateTime {
+        self.main_timestamp().datetime()
+    }
+    /// Verify if the date of the todo has a time, and not only a day
+    pub fn is_timed(&self) -> bool {
+        self.main_timestamp().start.time.is_some()
+    }
+    /// The next `n` occurrences of the todo strictly after `after`, following its repeater
+    pub fn next_occurrences(&self, after: NaiveDateTime, n: usize) -> Vec<NaiveDateTime> {
+        self.main_timestamp().next_occurrences(after, n)
+    }
+}
+impl fmt::Display for Todo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let title = &self.title;
+        let date = self.date();
+        write!(f, "{title},{date}")
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::Timestamp;
+    use std::path::Path;
+
+    #[test]
+    fn test_finding_date() {
+        let line0 = "*TODO this should be good <2023-08-08>";
+        let line1 = "*TODO this should be good <2023-08-08 Sun 10:10>";
+        let line2 = "*TODO this should be good too <2023-08-08 10:10>";
+        let line3 = "*TODO compare a < b <2023-08-08 Tue 10:10>";
+        let line4 = "*TODO this should be good < 2023-08-08 Tue>";
+        let date = |line| Timestamp::find(line).unwrap().datetime().to_string();
+        assert_eq!(date(line0), "2023-08-08 00:00:00");
+        assert_eq!(date(line1), "2023-08-08 10:10:00");
+        assert_eq!(date(line2), "2023-08-08 10:10:00");
+        assert_eq!(date(line3), "2023-08-08 10:10:00");
+        assert_eq!(date(line4), "2023-08-08 00:00:00")
+    }
+
+    #[test]
+    fn testing_parse_todo() {
+        let line0 = "*TODO this should be good <2023-08-08>";
+        let line1 = "*TODO this should be good <2023-08-08 Sun 10:10>";
+        let line2 = "*TODO this should be good too <2023-08-08 10:10>";
+        let line3 = "*TODO this should be no gucci <Sun 10:10>";
+        let line4 = "**TODO this should be no gucci <10:10>";
+        let good_lines = vec![line0, line1, line2];
+        let bad_lines = vec![line3, line4];
+        for lines in good_lines {
+            let x = super::Todo::parse_todo(lines);
+            assert!(x.is_some())
+        }
+        for lines in bad_lines {
+            let x = super::Todo::parse_todo(lines);
+            assert!(x.is_none())
+        }
+    }
+
+    #[test]
+    fn planning_on_next_line() {
+        let file = "#+TITLE: tasks\n* TODO Write report\n  SCHEDULED: <2023-09-05 Tue 10:00>\n* TODO No date\nSome text\n  DEADLINE: <2023-09-06 Wed>\n** TODO Nested\nDEADLINE: <2023-09-07 Thu>\n";
+        let todos = super::parse_file(Path::new("tasks.org"), file, &Default::default()).value;
+        let expected = vec![
+            "Write report,2023-09-05 10:00:00",
+            "Nested,2023-09-07 00:00:00",
+        ];
+        let todos: Vec<String> = todos.iter().map(|t| t.to_string()).collect();
+        assert_eq!(todos, expected);
+    }
+
+    #[test]
+    fn file_keywords() {
+        let file = "#+TODO: TODO NEXT WAITING | DONE CANCELLED\n* NEXT Call\nSCHEDULED: <2023-09-05 Tue>\n* WAITING Answer\nSCHEDULED: <2023-09-06 Wed>\n* DONE Report\nSCHEDULED: <2023-09-04 Mon>\n";
+        let todos = super::parse_file(Path::new("tasks.org"), file, &Default::default()).value;
+        let keywords: Vec<&str> = todos.iter().map(|t| t.keyword()).collect();
+        assert_eq!(keywords, ["NEXT", "WAITING"]);
+    }
+
+    #[test]
+    fn todo_fields() {
+        let heading = "** TODO [#A] Write report :work:urgent:";
+        let planning = "DEADLINE: <2023-09-05 Tue>";
+        let todo = super::Todo::parse_entry(heading, Some(planning)).unwrap();
+        assert_eq!(todo.level(), 2);
+        assert_eq!(todo.keyword(), "TODO");
+        assert_eq!(todo.priority(), Some('A'));
+        assert_eq!(todo.title(), "Write report");
+        assert_eq!(todo.tags(), ["work", "urgent"]);
+        assert_eq!(todo.deadline().unwrap().to_string(), "<2023-09-05 Tue>");
+        assert!(todo.scheduled().is_none());
+        assert_eq!(todo.next_occurrences(todo.date(), 1), []);
+    }
+
+    #[test]
+    fn invalid_planning_is_reported() {
+        let file =
+            "* TODO Good\nSCHEDULED: <2023-09-05 Tue>\n* TODO Bad\n  DEADLINE: <2023-09-31 Sun>\n";
+        let parsed = super::parse_file(Path::new("tasks.org"), file, &Default::default());
+        assert_eq!(parsed.value.len(), 1);
+        assert_eq!(parsed.errors.len(), 1);
+        assert_eq!(
+            parsed.errors[0].to_string(),
+            "tasks.org:4:14: invalid timestamp: expected a date as YYYY-MM-DD"
+        );
+    }
+
+    #[tokio::test]
+    async fn unreadable_files_are_reported() {
+        let dir = std::env::temp_dir().join(format!("orgparser-errors-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("good.org"),
+            "* TODO A\nSCHEDULED: <2030-01-01 Tue>\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("binary.org"), [0xff, 0xfe, 0x00]).unwrap();
+        std::os::unix::fs::symlink(dir.join("missing.org"), dir.join("broken.org")).unwrap();
+        // Emacs lock files are dangling symlinks, and must not be reported
+        std::os::unix::fs::symlink("user@host.1234", dir.join(".#good.org")).unwrap();
+        let parsed = super::generate_todos(&dir).await;
+        assert_eq!(parsed.value.len(), 1);
+        let mut errors: Vec<String> = parsed.errors.iter().map(|e| e.to_string()).collect();
+        errors.sort();
+        assert_eq!(errors.len(), 2, "{errors:?}");
+        assert!(errors[0].contains("binary.org: cannot read file"));
+        assert!(errors[1].contains("broken.org: cannot read file"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn org_paths() {
+        assert!(super::is_org_path(Path::new("/org/notes.org")));
+        assert!(!super::is_org_path(Path::new("/org/.#notes.org")));
+        assert!(!super::is_org_path(Path::new("/org/#notes.org#")));
+        assert!(!super::is_org_path(Path::new("/org/notes.org~")));
+    }
+
+    #[test]
+    fn headings_ignore_list_items_and_bold() {
+        let file = "* Top\n - *bold* item\n*bold* text\n** Child\n   CLOSED: [2023-09-05 Tue]\n";
+        let headings: Vec<super::HeadingLines> = super::headings(file).collect();
+        assert_eq!(headings.len(), 2);
+        assert_eq!(headings[0].planning, None);
+        assert_eq!(headings[1].heading, "** Child");
+        assert_eq!(headings[1].planning, Some("   CLOSED: [2023-09-05 Tue]"));
+        assert!(!super::is_heading("*TODOs are bold*"));
+        assert!(super::is_heading("*TODO: ranger ma chambre"));
+        let file = "* Top\n*TODOs are bold*\n** Child\n";
+        assert_eq!(super::headings(file).count(), 2);
+    }
+}
@@ -0,0 +1,549 @@
+//! Outline tree of an org file.
+//! A document is made of a preamble, i.e. the text before the first heading, and of headings.
+//! Each heading holds its planning, its property drawer, its section content and its
+//! sub-headings. Positions are byte offsets inside the source of the document.
+//! The section of a heading is the text between its heading line and its first sub-heading.
+//! The tree is lossless: the preamble, the heads, the bodies and the sub-headings cover every
+//! byte of the source once, in order, so writing the tree back gives the source unchanged,
+//! whitespace, comments, unknown elements and line endings included.
+use super::error::{ParseError, Parsed};
+use super::file::Origin;
+use super::heading::Heading as HeadingLine;
+use super::keywords::TodoKeywords;
+use super::logbook::Logbook;
+use super::planning::Planning;
+use super::properties::Properties;
+use super::tags::{file_tags, TagGroups};
+use super::{is_heading, is_planning, Todo, TodoVec};
+use std::fmt;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// A parsed org file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Document {
+    /// Path of the org file
+    pub path: PathBuf,
+    /// Content of the org file
+    pub source: String,
+    /// Keyword sequences of the file
+    pub keywords: TodoKeywords,
+    /// Properties of the "#+PROPERTY:" lines, inherited by every heading
+    pub properties: Properties,
+    /// Tags of the "#+FILETAGS:" lines, inherited by every heading
+    pub filetags: Vec<String>,
+    /// Tag groups of the "#+TAGS:" lines
+    pub tag_groups: TagGroups,
+    /// Text before the first heading, where the "#+" settings usually are
+    pub preamble: Range<usize>,
+    /// Top level headings
+    pub headings: Vec<Heading>,
+    /// State of the file when it was read, None when the document was not read from a file
+    pub origin: Option<Origin>,
+}
+
+/// A heading of a document, with its content and its sub-headings.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Heading {
+    /// Line number of the heading, starting at 1
+    pub line: usize,
+    /// Bytes of the heading and of all its sub-headings
+    pub span: Range<usize>,
+    /// Outline level, i.e. the number of stars
+    pub level: usize,
+    /// TODO keyword, if the first word of the heading is one
+    pub keyword: Option<String>,
+    /// Priority cookie. Example: 'A' for "[#A]"
+    pub priority: Option<char>,
+    /// Text of the heading, without keyword, priority and tags
+    pub title: String,
+    /// Tags at the end of the heading, without the colons
+    pub tags: Vec<String>,
+    /// Timestamps of the planning line
+    pub planning: Planning,
+    /// Properties of the ":PROPERTIES:" drawer, in order
+    pub properties: Vec<(String, String)>,
+    /// Bytes of the heading line, of its planning line and of its property drawer
+    pub head: Range<usize>,
+    /// Bytes of the planning line, line ending included
+    pub planning_line: Option<Range<usize>>,
+    /// Bytes of the property drawer, from ":PROPERTIES:" to ":END:", line ending included
+    pub drawer: Option<Range<usize>>,
+    /// Clock entries and notes of the section
+    pub logbook: Logbook,
+    /// Bytes of the content between the heading (planning and drawer included) and its first
+    /// sub-heading
+    pub section: Range<usize>,
+    /// Direct sub-headings
+    pub children: Vec<Heading>,
+}
+
+/// What a heading inherits from the file and from its parent headings.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Inherited {
+    /// Titles of the parent headings, from the top level one
+    pub outline: Vec<String>,
+    /// Properties of the file, overridden by the drawers of the parent headings
+    pub properties: Properties,
+    /// Tags of the file and of the parent headings
+    pub tags: Vec<String>,
+}
+
+/// Depth-first iterator over headings, parents first.
+pub struct Headings<'a> {
+    stack: Vec<&'a Heading>,
+}
+
+impl Document {
+    /// Parse the content of an org file.
+    /// `keywords` is used when the file does not define its own "#+TODO:" sequences.
+    pub fn parse(path: &Path, source: String, keywords: &TodoKeywords) -> Parsed<Document> {
+        let keywords = TodoKeywords::from_file(&source, keywords);
+        let properties = Properties::from_file(&source);
+        let filetags = file_tags(&source);
+        let tag_groups = TagGroups::from_file(&source);
+        let mut errors = vec![];
+        let lines = lines(&source);
+        let mut headings = vec![];
+        let mut open: Vec<Heading> = vec![];
+        let mut preamble = 0..0;
+        let mut index = 0;
+        while let Some(&(start, line)) = lines.get(index) {
+            let end = line_start(&lines, index + 1, &source);
+            index += 1;
+            if !is_heading(line) {
+                match open.last_mut() {
+                    Some(heading) => heading.section.end = end,
+                    None => preamble.end = end,
+                }
+                continue;
+            }
+            let headline = HeadingLine::parse_with_keywords(line, &keywords.names())
+                .expect("a heading line is a heading");
+            close(&mut open, &mut headings, headline.level, &source[..start]);
+            let mut heading = Heading::new(headline, index, start..end);
+            let planning = lines.get(index).filter(|(_, l)| is_planning(l));
+            heading.planning = match planning {
+                Some(&(planning_start, planning)) => {
+                    let (planning, timestamp_errors) = Planning::parse_with_errors(planning);
+                    errors.extend(timestamp_errors.into_iter().map(|source| {
+                        ParseError::Timestamp {
+                            path: path.to_path_buf(),
+                            line: index + 1,
+                            column: source.column + 1,
+                            source,
+                        }
+                    }));
+                    index += 1;
+                    heading.planning_line =
+                        Some(planning_start..line_start(&lines, index, &source));
+                    planning
+                }
+                // The first version of the parser read the timestamps on the heading line
+                None => Planning::parse(line),
+            };
+            if let Some((properties, length)) = property_drawer(&lines[index..]) {
+                let drawer_start = line_start(&lines, index, &source);
+                heading.properties = properties;
+                index += length;
+                heading.drawer = Some(drawer_start..line_start(&lines, index, &source));
+            }
+            let end = line_start(&lines, index, &source);
+            heading.head = start..end;
+            heading.section = start..end;
+            heading.span.end = end;
+            open.push(heading);
+        }
+        close(&mut open, &mut headings, 1, &source);
+        Parsed {
+            value: Document {
+                path: path.to_path_buf(),
+                source,
+                keywords,
+                properties,
+                filetags,
+                tag_groups,
+                preamble,
+                headings,
+                origin: None,
+            },
+            errors,
+        }
+    }
+    /// Text of a range of the document, e.g. of a heading span or section.
+    pub fn text(&self, range: &Range<usize>) -> &str {
+        &self.source[range.clone()]
+    }
+    /// Every heading of the document, parents first.
+    pub fn iter(&self) -> Headings<'_> {
+        Headings::new(&self.headings)
+    }
+    /// The todos of the document, with their outline path, inherited properties and tags.
+    pub fn todos(&self) -> TodoVec {
+        let mut todos = vec![];
+        self.visit(|heading, inherited| {
+            todos.extend(Todo::from_heading(heading, inherited, self));
+        });
+        todos
+    }
+    /// Find a heading by its outline path, i.e. the titles of its parents and its own title
+    /// joined by " / ". Example: "Work / Meetings"
+    pub fn find(&self, outline_path: &str) -> Option<&Heading> {
+        let title = outline_path.rsplit(" / ").next().unwrap_or(outline_path);
+        let mut found = None;
+        self.visit(|heading, inherited| {
+            if found.is_some() || heading.title != title {
+                return;
+            }
+            let mut outline = inherited.outline.clone();
+            outline.push(heading.title.clone());
+            if outline.join(" / ") == outline_path {
+                found = Some(heading);
+            }
+        });
+        found
+    }
+    /// Call `f` on every heading of the document, parents first, with what the heading
+    /// inherits from the file and from its parents.
+    pub fn visit<'a, F>(&'a self, mut f: F)
+    where
+        F: FnMut(&'a Heading, &Inherited),
+    {
+        let inherited = Inherited {
+            outline: vec![],
+            properties: self.properties.clone(),
+            tags: self.filetags.clone(),
+        };
+        for heading in &self.headings {
+            visit(heading, &inherited, &mut f);
+        }
+    }
+}
+
+impl fmt::Display for Document {
+    /// Write the document from its tree: the preamble, then every heading with its heading
+    /// line, planning line, property drawer, body and sub-headings.
+    /// An unmodified document gives back its source, byte for byte.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.text(&self.preamble))?;
+        for heading in &self.headings {
+            self.write_heading(f, heading)?;
+        }
+        Ok(())
+    }
+}
+
+impl Document {
+    fn write_heading(&self, f: &mut fmt::Formatter, heading: &Heading) -> fmt::Result {
+        f.write_str(self.text(&heading.headline()))?;
+        for range in [&heading.planning_line, &heading.drawer]
+            .into_iter()
+            .flatten()
+        {
+            f.write_str(self.text(range))?;
+        }
+        f.write_str(self.text(&heading.body()))?;
+        for child in &heading.children {
+            self.write_heading(f, child)?;
+        }
+        Ok(())
+    }
+}
+
+fn visit<'a, F>(heading: &'a Heading, inherited: &Inherited, f: &mut F)
+where
+    F: FnMut(&'a Heading, &Inherited),
+{
+    f(heading, inherited);
+    if heading.children.is_empty() {
+        return;
+    }
+    let mut inherited = inherited.clone();
+    inherited.outline.push(heading.title.clone());
+    inherited.properties.extend(&heading.properties);
+    for tag in &heading.tags {
+        if !inherited.tags.contains(tag) {
+            inherited.tags.push(tag.clone());
+        }
+    }
+    for child in &heading.children {
+        visit(child, &inherited, f);
+    }
+}
+
+impl Heading {
+    fn new(headline: HeadingLine, line: usize, span: Range<usize>) -> Heading {
+        let HeadingLine {
+            level,
+            keyword,
+            priority,
+            title,
+            tags,
+        } = headline;
+        Heading {
+            line,
+            head: span.clone(),
+            section: span.clone(),
+            span,
+            level,
+            keyword,
+            priority,
+            title,
+            tags,
+            ..Default::default()
+        }
+    }
+    /// Bytes of the heading line, line ending included.
+    pub fn headline(&self) -> Range<usize> {
+        let end = self
+            .planning_line
+            .iter()
+            .chain(&self.drawer)
+            .next()
+            .map_or(self.head.end, |r| r.start);
+        self.head.start..end
+    }
+    /// Bytes of the section after the head, i.e. of the notes, logbook and other elements.
+    pub fn body(&self) -> Range<usize> {
+        self.head.end..self.section.end
+    }
+    /// Value of a property of the drawer. Property names are case-insensitive.
+    pub fn property(&self, name: &str) -> Option<&str> {
+        self.properties
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+    /// The heading and all its sub-headings, parents first.
+    pub fn iter(&self) -> Headings<'_> {
+        Headings::new(std::slice::from_ref(self))
+    }
+}
+
+impl<'a> Headings<'a> {
+    fn new(headings: &'a [Heading]) -> Headings<'a> {
+        Headings {
+            stack: headings.iter().rev().collect(),
+        }
+    }
+}
+
+impl<'a> Iterator for Headings<'a> {
+    type Item = &'a Heading;
+    fn next(&mut self) -> Option<&'a Heading> {
+        let heading = self.stack.pop()?;
+        self.stack.extend(heading.children.iter().rev());
+        Some(heading)
+    }
+}
+
+/// Split the source in lines, keeping the byte offset of each line.
+/// Line endings ("\n" or "\r\n") are not part of the lines.
+fn lines(source: &str) -> Vec<(usize, &str)> {
+    let mut offset = 0;
+    source
+        .split_inclusive('\n')
+        .map(|line| {
+            let start = offset;
+            offset += line.len();
+            (start, line.trim_end_matches(['\n', '\r']))
+        })
+        .collect()
+}
+
+/// Byte offset of the line `index`, or the end of the source after the last line.
+fn line_start(lines: &[(usize, &str)], index: usize, source: &str) -> usize {
+    lines.get(index).map_or(source.len(), |(start, _)| *start)
+}
+
+/// Close the open headings of level `level` or deeper, which end at the end of `source`, and
+/// attach them to their parent. Their sections are complete, so their logbook is read.
+fn close(open: &mut Vec<Heading>, headings: &mut Vec<Heading>, level: usize, source: &str) {
+    while open.last().is_some_and(|h| h.level >= level) {
+        let mut heading = open.pop().expect("an open heading");
+        heading.span.end = source.len();
+        heading.logbook = Logbook::parse(&source[heading.section.clone()]);
+        match open.last_mut() {
+            Some(parent) => parent.children.push(heading),
+            None => headings.push(heading),
+        }
+    }
+}
+
+/// Parse the property drawer at the start of `lines`.
+/// Returns the properties and the number of lines of the drawer, ":END:" included, or None if
+/// there is no drawer or if it is not closed before the next heading.
+fn property_drawer(lines: &[(usize, &str)]) -> Option<(Vec<(String, String)>, usize)> {
+    let (_, first) = lines.first()?;
+    if !first.trim().eq_ignore_ascii_case(":PROPERTIES:") {
+        return None;
+    }
+    let mut properties = vec![];
+    for (index, (_, line)) in lines.iter().enumerate().skip(1) {
+        let line = line.trim();
+        if line.eq_ignore_ascii_case(":END:") {
+            return Some((properties, index + 1));
+        }
+        if is_heading(line) {
+            return None;
+        }
+        let property = line
+            .strip_prefix(':')
+            .and_then(|l| l.split_once(':'))
+            .filter(|(key, _)| !key.is_empty() && !key.contains(char::is_whitespace));
+        if let Some((key, value)) = property {
+            properties.push((String::from(key), String::from(value.trim())));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Document;
+    use crate::parsing::edit::{set_keyword, set_planning, set_property, Edit};
+    use std::path::Path;
+
+    const FILE: &str = "#+TITLE: Projects\n* Projects\n** Website :web:\n:PROPERTIES:\n:ID: website\n:Owner: me\n:END:\nNotes\n*** TODO Deploy\nSCHEDULED: <2023-09-05 Tue>\nBody\n** TODO Taxes\nDEADLINE: <2023-09-30 Sat>\n* Inbox\n";
+
+    fn parse(source: &str) -> Document {
+        let parsed = Document::parse(
+            Path::new("projects.org"),
+            source.into(),
+            &Default::default(),
+        );
+        assert!(parsed.errors.is_empty());
+        parsed.value
+    }
+
+    #[test]
+    fn outline_tree() {
+        let doc = parse(FILE);
+        assert_eq!(doc.text(&doc.preamble), "#+TITLE: Projects\n");
+        let titles: Vec<&str> = doc.headings.iter().map(|h| h.title.as_str()).collect();
+        assert_eq!(titles, ["Projects", "Inbox"]);
+        let website = &doc.headings[0].children[0];
+        assert_eq!(website.line, 3);
+        assert_eq!(website.tags, ["web"]);
+        assert_eq!(website.property("id"), Some("website"));
+        assert_eq!(website.property("OWNER"), Some("me"));
+        assert_eq!(
+            doc.text(&website.section),
+            "** Website :web:\n:PROPERTIES:\n:ID: website\n:Owner: me\n:END:\nNotes\n"
+        );
+        assert_eq!(
+            doc.text(website.drawer.as_ref().unwrap()),
+            ":PROPERTIES:\n:ID: website\n:Owner: me\n:END:\n"
+        );
+        assert_eq!(doc.text(&website.body()), "Notes\n");
+        let deploy = &website.children[0];
+        assert_eq!(deploy.line, 9);
+        assert_eq!(
+            doc.text(&deploy.head),
+            "*** TODO Deploy\nSCHEDULED: <2023-09-05 Tue>\n"
+        );
+        assert!(deploy.planning.scheduled.is_some());
+        assert_eq!(
+            doc.text(&deploy.span),
+            "*** TODO Deploy\nSCHEDULED: <2023-09-05 Tue>\nBody\n"
+        );
+        assert_eq!(doc.find("Projects / Website / Deploy"), Some(deploy));
+        assert_eq!(doc.find("Website / Deploy"), None);
+        let all: Vec<&str> = doc.iter().map(|h| h.title.as_str()).collect();
+        assert_eq!(all, ["Projects", "Website", "Deploy", "Taxes", "Inbox"]);
+        assert_eq!(doc.headings[0].iter().count(), 4);
+    }
+
+    #[test]
+    fn outline_paths() {
+        let doc = parse(FILE);
+        let paths: Vec<String> = doc.todos().iter().map(|t| t.outline_path()).collect();
+        assert_eq!(paths, ["Projects / Website / Deploy", "Projects / Taxes"]);
+    }
+
+    #[test]
+    fn inherited_properties() {
+        let file = "#+PROPERTY: LOCATION Home\n#+PROPERTY: Effort_ALL 0:10 1:00\n* Work\n:PROPERTIES:\n:LOCATION: Office\n:VAR: a\n:END:\n** TODO Meeting\nSCHEDULED: <2023-09-05 Tue>\n:PROPERTIES:\n:ID: meeting-1\n:VAR+: b\n:END:\n* TODO Dishes\nSCHEDULED: <2023-09-05 Tue>\n";
+        let todos = parse(file).todos();
+        let meeting = &todos[0];
+        assert_eq!(meeting.property("id"), Some("meeting-1"));
+        assert_eq!(meeting.property("LOCATION"), None);
+        assert_eq!(meeting.inherited_property("LOCATION"), Some("Office"));
+        assert_eq!(meeting.inherited_property("VAR"), Some("a b"));
+        assert_eq!(meeting.allowed_values("Effort"), ["0:10", "1:00"]);
+        assert_eq!(todos[1].inherited_property("location"), Some("Home"));
+        assert_eq!(todos[1].properties().len(), 0);
+    }
+
+    #[test]
+    fn inherited_tags() {
+        let file = "#+FILETAGS: :projects:\n#+TAGS: [ Work : meeting report ] { @home @office }\n* Website :web:\n** TODO Deploy :@office:meeting:\nSCHEDULED: <2023-09-05 Tue>\n";
+        let todos = parse(file).todos();
+        let deploy = &todos[0];
+        assert_eq!(deploy.tags(), ["@office", "meeting"]);
+        assert_eq!(deploy.inherited_tags(), ["projects", "web"]);
+        assert_eq!(deploy.all_tags(), ["@office", "meeting", "projects", "web"]);
+        assert!(deploy.has_tag("web"));
+        assert!(deploy.has_tag("Work"));
+        assert!(!deploy.has_tag("@home"));
+    }
+
+    #[test]
+    fn unclosed_drawer_and_crlf() {
+        let doc =
+            parse("* TODO A\r\nSCHEDULED: <2023-09-05 Tue>\r\n:PROPERTIES:\r\n:ID: a\r\n* B\r\n");
+        assert!(doc.headings[0].properties.is_empty());
+        assert!(doc.headings[0].planning.scheduled.is_some());
+        assert_eq!(doc.text(&doc.headings[1].span), "* B\r\n");
+    }
+
+    /// Edits setting every keyword, planning timestamp and property of `doc` to its own value.
+    fn no_op_edits(doc: &Document) -> Vec<Edit> {
+        let mut edits = vec![];
+        for heading in doc.iter() {
+            if let Some(keyword) = &heading.keyword {
+                edits.push(set_keyword(doc, heading, keyword));
+            }
+            let planning = &heading.planning;
+            let timestamps = [
+                ("SCHEDULED:", &planning.scheduled),
+                ("DEADLINE:", &planning.deadline),
+                ("CLOSED:", &planning.closed),
+            ];
+            for (keyword, timestamp) in timestamps {
+                if let Some(timestamp) = timestamp {
+                    edits.push(set_planning(doc, heading, keyword, timestamp));
+                }
+            }
+            for (name, value) in &heading.properties {
+                edits.push(set_property(doc, heading, name, value));
+            }
+        }
+        edits
+    }
+
+    #[test]
+    fn lossless_corpus() {
+        let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+        let mut count = 0;
+        for entry in std::fs::read_dir(corpus).unwrap() {
+            let path = entry.unwrap().path();
+            let source = std::fs::read_to_string(&path).unwrap();
+            let doc = Document::parse(&path, source.clone(), &Default::default()).value;
+            let name = path.display();
+            assert_eq!(doc.to_string(), source, "{name}");
+            for heading in doc.iter() {
+                let line = source[..heading.span.start].matches('\n').count() + 1;
+                assert_eq!(heading.line, line, "{name}: {}", heading.title);
+            }
+            let edits = no_op_edits(&doc);
+            assert_eq!(doc.apply(&edits), source, "{name}");
+            assert_eq!(doc.edit(&edits).value.todos(), doc.todos(), "{name}");
+            count += 1;
+        }
+        assert!(count >= 6);
+    }
+}
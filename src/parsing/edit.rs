@@ -0,0 +1,260 @@
+//! Edits of an org file, as byte ranges of its source replaced by new text.
+//! Only the bytes of the edits change: the rest of the file, its indentation and its line
+//! endings are kept as they are. The functions below build the edits of common changes of a
+//! heading, such as a new keyword, planning timestamp, property or logbook note.
+use super::document::{Document, Heading};
+use super::error::Parsed;
+use super::file::{self, LockPolicy, WriteError};
+use super::timestamp::Timestamp;
+use std::ops::Range;
+
+/// Name of the drawer of the clocks and notes.
+const LOGBOOK_DRAWER: &str = ":LOGBOOK:";
+
+/// Replacement of a byte range of a source. An empty range inserts text.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Edit {
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+impl Edit {
+    pub fn replace(range: Range<usize>, text: impl Into<String>) -> Edit {
+        Edit {
+            range,
+            text: text.into(),
+        }
+    }
+    pub fn insert(at: usize, text: impl Into<String>) -> Edit {
+        Self::replace(at..at, text)
+    }
+}
+
+impl Document {
+    /// Source of the document with `edits` applied. Edits must not overlap, and insertions at
+    /// the same offset are made in the order of `edits`.
+    pub fn apply(&self, edits: &[Edit]) -> String {
+        let mut edits: Vec<&Edit> = edits.iter().collect();
+        edits.sort_by_key(|e| e.range.start);
+        let mut source = String::with_capacity(self.source.len());
+        let mut copied = 0;
+        for edit in edits {
+            source.push_str(&self.source[copied..edit.range.start.max(copied)]);
+            source.push_str(&edit.text);
+            copied = copied.max(edit.range.end);
+        }
+        source.push_str(&self.source[copied..]);
+        source
+    }
+    /// The document with `edits` applied, parsed again. It keeps the state of the file it was
+    /// read from, which is checked when it is saved.
+    pub fn edit(&self, edits: &[Edit]) -> Parsed<Document> {
+        let mut parsed = Document::parse(&self.path, self.apply(edits), &self.keywords);
+        parsed.value.origin = self.origin.clone();
+        parsed
+    }
+    /// Write the source of the document to its file, unless Emacs locked it or it changed since
+    /// it was read. See `file::write`.
+    pub fn save(&self) -> Result<(), WriteError> {
+        self.save_with(LockPolicy::Refuse)
+    }
+    /// Write the source of the document to its file, with a policy for the Emacs locks.
+    pub fn save_with(&self, lock: LockPolicy) -> Result<(), WriteError> {
+        file::write(&self.path, &self.source, self.origin.as_ref(), lock)
+    }
+    /// Check that the file of the document can be written, like `save_with` does first.
+    pub fn check_writable(&self, lock: LockPolicy) -> Result<(), WriteError> {
+        file::check(&self.path, self.origin.as_ref(), lock)
+    }
+    /// Line ending of the document: "\r\n" if its first line ends with it, else "\n".
+    pub fn newline(&self) -> &'static str {
+        match self.source.split_once('\n') {
+            Some((line, _)) if line.ends_with('\r') => "\r\n",
+            _ => "\n",
+        }
+    }
+    /// Insert `lines`, given without final line ending, after the lines of `range`.
+    /// When `range` ends the file without line ending, the file still ends without one.
+    fn insert_after(&self, range: &Range<usize>, lines: &str) -> Edit {
+        let newline = self.newline();
+        match self.text(range).ends_with('\n') || range.is_empty() {
+            true => Edit::insert(range.end, format!("{lines}{newline}")),
+            false => Edit::insert(range.end, format!("{newline}{lines}")),
+        }
+    }
+}
+
+/// Replace the keyword of a heading, or add one.
+pub fn set_keyword(doc: &Document, heading: &Heading, keyword: &str) -> Edit {
+    let start = heading.head.start;
+    let line = doc.text(&heading.headline());
+    let stars = line.len() - line.trim_start_matches('*').len();
+    let rest = &line[stars..];
+    let word_start = start + stars + rest.len() - rest.trim_start().len();
+    match &heading.keyword {
+        Some(old) => Edit::replace(word_start..word_start + old.len(), keyword),
+        None => Edit::insert(start + stars, format!(" {keyword}")),
+    }
+}
+
+/// Set the timestamp following `keyword` ("SCHEDULED:", "DEADLINE:" or "CLOSED:") in the
+/// planning line of a heading. Only the bytes of the timestamp are replaced when it exists.
+/// Otherwise CLOSED is added at the start of the planning line and the others at its end, as
+/// org does, and a planning line is added when there is none.
+pub fn set_planning(doc: &Document, heading: &Heading, keyword: &str, ts: &Timestamp) -> Edit {
+    let Some(range) = &heading.planning_line else {
+        return doc.insert_after(&heading.headline(), &format!("{keyword} {ts}"));
+    };
+    let line = doc.text(range);
+    if let Some(old) = planning_timestamp(line, keyword) {
+        return Edit::replace(
+            range.start + old.start..range.start + old.end,
+            ts.to_string(),
+        );
+    }
+    let content = line.trim_end_matches(['\n', '\r']);
+    if keyword == "CLOSED:" {
+        let indent = content.len() - content.trim_start().len();
+        Edit::insert(range.start + indent, format!("{keyword} {ts} "))
+    } else {
+        Edit::insert(range.start + content.len(), format!(" {keyword} {ts}"))
+    }
+}
+
+/// Byte range of the timestamp following `keyword` in a planning line.
+pub fn planning_timestamp(line: &str, keyword: &str) -> Option<Range<usize>> {
+    let (before, rest) = line.split_once(keyword)?;
+    let trimmed = rest.trim_start();
+    let start = before.len() + keyword.len() + rest.len() - trimmed.len();
+    let (_, after) = Timestamp::parse(trimmed).ok()?;
+    Some(start..start + trimmed.len() - after.len())
+}
+
+/// Set a property in the drawer of a heading. The value of an existing property is replaced,
+/// a new property is added at the end of the drawer, and a drawer is added when there is none.
+pub fn set_property(doc: &Document, heading: &Heading, name: &str, value: &str) -> Edit {
+    let newline = doc.newline();
+    let Some(drawer) = &heading.drawer else {
+        let before = heading.planning_line.clone().unwrap_or(heading.headline());
+        let text = format!(":PROPERTIES:{newline}:{name}: {value}{newline}:END:");
+        return doc.insert_after(&before, &text);
+    };
+    let mut end = drawer.start;
+    for (offset, line) in lines(doc, drawer) {
+        let trimmed = line.trim_start();
+        let property = trimmed
+            .strip_prefix(':')
+            .and_then(|l| l.split_once(':'))
+            .filter(|(key, _)| key.eq_ignore_ascii_case(name));
+        if let Some((_, old)) = property {
+            let content = old.trim_end_matches(['\n', '\r']);
+            let value_start = offset + line.len() - old.len();
+            let text = match content.is_empty() {
+                true => format!(" {value}"),
+                false => String::from(value),
+            };
+            // Blanks around the value are kept
+            let blank = content.len() - content.trim_start().len();
+            let end = blank + content.trim().len();
+            return Edit::replace(value_start + blank..value_start + end, text);
+        }
+        if trimmed.trim_end().eq_ignore_ascii_case(":END:") {
+            end = offset;
+        }
+    }
+    let line = doc.text(&(drawer.start..end));
+    let indent = &line[..line.len() - line.trim_start().len()];
+    Edit::insert(end, format!("{indent}:{name}: {value}{newline}"))
+}
+
+/// Add a note at the top of the logbook of a heading, where org puts the most recent notes.
+/// A ":LOGBOOK:" drawer is added after the property drawer when there is none.
+/// `note` is the text of the note, without line ending. Example: "- Note taken on [...]"
+pub fn add_logbook_note(doc: &Document, heading: &Heading, note: &str) -> Edit {
+    let newline = doc.newline();
+    let logbook = lines(doc, &heading.body())
+        .find(|(_, line)| line.trim().eq_ignore_ascii_case(LOGBOOK_DRAWER));
+    match logbook {
+        Some((offset, line)) => {
+            let indent = &line[..line.len() - line.trim_start().len()];
+            Edit::insert(offset + line.len(), format!("{indent}{note}{newline}"))
+        }
+        None => {
+            let text = format!("{LOGBOOK_DRAWER}{newline}{note}{newline}:END:");
+            doc.insert_after(&heading.head, &text)
+        }
+    }
+}
+
+/// Add an entry, given without final line ending, as the last sub-heading of `parent`, or at
+/// the end of the file.
+pub fn append_entry(doc: &Document, parent: Option<&Heading>, entry: &str) -> Edit {
+    let range = parent.map_or(0..doc.source.len(), |p| p.span.clone());
+    doc.insert_after(&range, entry)
+}
+
+/// The lines of a range of the document, with their offsets. Line endings are kept.
+fn lines<'a>(
+    doc: &'a Document,
+    range: &Range<usize>,
+) -> impl Iterator<Item = (usize, &'a str)> + 'a {
+    let mut offset = range.start;
+    doc.text(range).split_inclusive('\n').map(move |line| {
+        let start = offset;
+        offset += line.len();
+        (start, line)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{add_logbook_note, set_keyword, set_planning, set_property};
+    use crate::parsing::document::Document;
+    use crate::parsing::timestamp::Timestamp;
+    use std::path::Path;
+
+    fn parse(source: &str) -> Document {
+        Document::parse(Path::new("a.org"), source.into(), &Default::default()).value
+    }
+
+    #[test]
+    fn heading_edits() {
+        let doc = parse("* TODO  Deploy  :ops:\r\n  SCHEDULED: <2023-09-05 Tue>\r\n  :PROPERTIES:\r\n  :ID:   deploy\r\n  :END:\r\nNotes\r\n* Next");
+        let heading = &doc.headings[0];
+        let (ts, _) = Timestamp::parse("<2023-09-12 Tue +1w>").unwrap();
+        let edits = [
+            set_keyword(&doc, heading, "DONE"),
+            set_planning(&doc, heading, "SCHEDULED:", &ts),
+            set_planning(&doc, heading, "DEADLINE:", &ts),
+            set_property(&doc, heading, "ID", "new"),
+            set_property(&doc, heading, "LAST_REPEAT", "[2023-09-05 Tue]"),
+            add_logbook_note(&doc, heading, "- Note"),
+        ];
+        assert_eq!(
+            doc.apply(&edits),
+            "* DONE  Deploy  :ops:\r\n  SCHEDULED: <2023-09-12 Tue +1w> DEADLINE: <2023-09-12 Tue +1w>\r\n  :PROPERTIES:\r\n  :ID:   new\r\n  :LAST_REPEAT: [2023-09-05 Tue]\r\n  :END:\r\n:LOGBOOK:\r\n- Note\r\n:END:\r\nNotes\r\n* Next"
+        );
+    }
+
+    #[test]
+    fn missing_elements() {
+        let doc = parse("* Heading\n* Last");
+        let last = &doc.headings[1];
+        let (ts, _) = Timestamp::parse("[2023-09-05 Tue 10:00]").unwrap();
+        let edits = [
+            set_keyword(&doc, last, "DONE"),
+            set_planning(&doc, last, "CLOSED:", &ts),
+            set_property(&doc, last, "ID", "last"),
+        ];
+        assert_eq!(
+            doc.apply(&edits),
+            "* Heading\n* DONE Last\nCLOSED: [2023-09-05 Tue 10:00]\n:PROPERTIES:\n:ID: last\n:END:"
+        );
+        let doc = parse("* Heading\n:LOGBOOK:\n- Old\n:END:\n");
+        let edit = add_logbook_note(&doc, &doc.headings[0], "- New");
+        assert_eq!(
+            doc.apply(&[edit]),
+            "* Heading\n:LOGBOOK:\n- New\n- Old\n:END:\n"
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! Errors met while walking the org directory and parsing the org files.
+//! Parsing never stops at the first error: the functions of the parsing module return what they
+//! could parse along with the errors, as a `Parsed` value.
+use super::timestamp::TimestampError;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// An error of the parsing module, with the position of the failure.
+#[derive(Debug)]
+pub enum ParseError {
+    /// A directory of the org directory cannot be walked, e.g. because of its permissions
+    Walk {
+        path: PathBuf,
+        source: walkdir::Error,
+    },
+    /// An org file cannot be read, e.g. because it is a broken symlink or is not UTF-8
+    Read { path: PathBuf, source: io::Error },
+    /// A planning line holds an invalid timestamp. Lines and columns start at 1
+    Timestamp {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        source: TimestampError,
+    },
+}
+
+/// A parsed value, possibly partial, and the errors met while parsing it.
+#[derive(Debug)]
+pub struct Parsed<T> {
+    pub value: T,
+    pub errors: Vec<ParseError>,
+}
+
+impl ParseError {
+    /// Path of the file or directory where the error happened
+    pub fn path(&self) -> &Path {
+        match self {
+            ParseError::Walk { path, .. }
+            | ParseError::Read { path, .. }
+            | ParseError::Timestamp { path, .. } => path,
+        }
+    }
+    /// Line of the error, if it happened inside a file
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            ParseError::Timestamp { line, .. } => Some(*line),
+            _ => None,
+        }
+    }
+    /// Column of the error, if it happened inside a file
+    pub fn column(&self) -> Option<usize> {
+        match self {
+            ParseError::Timestamp { column, .. } => Some(*column),
+            _ => None,
+        }
+    }
+}
+
+impl<T> Parsed<T> {
+    pub fn new(value: T) -> Parsed<T> {
+        Parsed {
+            value,
+            errors: vec![],
+        }
+    }
+    /// Transform the value, keeping the errors
+    pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> Parsed<U> {
+        Parsed {
+            value: f(self.value),
+            errors: self.errors,
+        }
+    }
+}
+
+impl<T: Default> Default for Parsed<T> {
+    fn default() -> Self {
+        Parsed::new(T::default())
+    }
+}
+
+impl fmt::Display for ParseError {
+    /// Write the error as "path:line:column: message", like compilers do
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.path().display())?;
+        if let (Some(line), Some(column)) = (self.line(), self.column()) {
+            write!(f, ":{line}:{column}")?;
+        }
+        match self {
+            ParseError::Walk { source, .. } => match source.io_error() {
+                Some(e) => write!(f, ": cannot walk directory: {e}"),
+                None => write!(f, ": cannot walk directory: {source}"),
+            },
+            ParseError::Read { source, .. } => write!(f, ": cannot read file: {source}"),
+            ParseError::Timestamp { source, .. } => {
+                write!(f, ": invalid timestamp: {}", source.reason)
+            }
+        }
+    }
+}
+
+impl Error for ParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParseError::Walk { source, .. } => Some(source),
+            ParseError::Read { source, .. } => Some(source),
+            ParseError::Timestamp { source, .. } => Some(source),
+        }
+    }
+}
@@ -0,0 +1,355 @@
+//! Reading and writing of org files while Emacs may have them open.
+//! The state of a file is recorded when it is read, and checked before it is written: a file
+//! changed since is not overwritten, nor is a file that Emacs locked because it has unsaved
+//! changes in a buffer. Files are written to a temporary file renamed over them, so that a
+//! reader never sees half a file, and keep their permissions and final line ending.
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Lines of context around the changes of a diff.
+const CONTEXT: usize = 3;
+
+/// State of a file when it was read.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Origin {
+    /// Modification time, None when the file did not exist
+    pub modified: Option<SystemTime>,
+    pub len: u64,
+    /// FNV-1a hash of the content
+    pub hash: u64,
+    /// Whether the content ended with a line ending
+    pub final_newline: bool,
+}
+
+/// What to do when Emacs has locked a file.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum LockPolicy {
+    /// Do not write the file
+    #[default]
+    Refuse,
+    /// Write the file anyway
+    Ignore,
+}
+
+/// Error returned when a file cannot be written.
+#[derive(Debug)]
+pub enum WriteError {
+    Io(PathBuf, io::Error),
+    /// Emacs has unsaved changes of the file, in the session of this owner ("user@host.pid")
+    Locked(PathBuf, String),
+    /// The file changed since it was read
+    Changed(PathBuf),
+}
+
+impl Origin {
+    /// State of a file read with the content `source`. `modified` is the modification time
+    /// read before the content, so that a change while reading is seen as a change.
+    pub fn new(modified: Option<SystemTime>, source: &str) -> Origin {
+        Origin {
+            modified,
+            len: source.len() as u64,
+            hash: hash(source.as_bytes()),
+            final_newline: source.ends_with('\n'),
+        }
+    }
+    /// Whether the file at `path` is still as it was read. A file with another size changed,
+    /// else its content is compared: an equal modification time is not enough, as it comes
+    /// from a coarse clock and two writes in a row can share it, and a file touched without
+    /// being changed is still current.
+    pub fn is_current(&self, path: &Path) -> io::Result<bool> {
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(self.modified.is_none()),
+            Err(e) => return Err(e),
+        };
+        if metadata.len() != self.len {
+            return Ok(false);
+        }
+        Ok(hash(&fs::read(path)?) == self.hash)
+    }
+}
+
+/// Read a file with its state. A missing file is an error.
+pub fn read(path: &Path) -> io::Result<(String, Origin)> {
+    let modified = fs::metadata(path)?.modified().ok();
+    let source = fs::read_to_string(path)?;
+    let origin = Origin::new(modified, &source);
+    Ok((source, origin))
+}
+
+/// Read a file with its state, without blocking the runtime. A missing file is an error.
+pub async fn read_async(path: &Path) -> io::Result<(String, Origin)> {
+    let modified = tokio::fs::metadata(path).await?.modified().ok();
+    let source = tokio::fs::read_to_string(path).await?;
+    let origin = Origin::new(modified, &source);
+    Ok((source, origin))
+}
+
+/// Owner of the Emacs lock of a file: the target of the ".#NAME" symbolic link next to it,
+/// as "user@host.pid:boot-time", or the content of a lock file where links are not supported.
+pub fn lock_owner(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_str()?;
+    let lock = path.with_file_name(format!(".#{name}"));
+    let owner = match fs::read_link(&lock) {
+        Ok(target) => target.to_string_lossy().into_owned(),
+        Err(_) => fs::read_to_string(&lock).ok()?,
+    };
+    let owner = owner.trim();
+    Some(String::from(
+        owner.split_once(':').map_or(owner, |(o, _)| o),
+    ))
+}
+
+/// Write `text` to `path`, checking the lock of the file and, when given, that it did not
+/// change since `origin`. The text gets a final line ending when the file had one, and a
+/// symbolic link is written through.
+pub fn write(
+    path: &Path,
+    text: &str,
+    origin: Option<&Origin>,
+    lock: LockPolicy,
+) -> Result<(), WriteError> {
+    let io_error = |e| WriteError::Io(path.to_path_buf(), e);
+    check(path, origin, lock)?;
+    let mut text = String::from(text);
+    if origin.is_some_and(|o| o.final_newline) && !text.is_empty() && !text.ends_with('\n') {
+        text.push_str(if text.contains("\r\n") { "\r\n" } else { "\n" });
+    }
+    let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    atomic_write(&target, text.as_bytes()).map_err(io_error)
+}
+
+/// Check that `path` can be written: it is not locked by Emacs, unless `lock` ignores the locks,
+/// and it did not change since `origin` when given.
+pub fn check(path: &Path, origin: Option<&Origin>, lock: LockPolicy) -> Result<(), WriteError> {
+    if lock == LockPolicy::Refuse {
+        if let Some(owner) = lock_owner(path) {
+            return Err(WriteError::Locked(path.to_path_buf(), owner));
+        }
+    }
+    if let Some(origin) = origin {
+        let current = origin
+            .is_current(path)
+            .map_err(|e| WriteError::Io(path.to_path_buf(), e))?;
+        if !current {
+            return Err(WriteError::Changed(path.to_path_buf()));
+        }
+    }
+    Ok(())
+}
+
+/// Write to a hidden temporary file next to `path`, then rename it over `path`.
+pub(crate) fn atomic_write(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temporary = path.with_file_name(format!(".{name}.{}.tmp", std::process::id()));
+    let result = (|| {
+        let mut file = fs::File::create(&temporary)?;
+        file.write_all(bytes)?;
+        if let Ok(metadata) = fs::metadata(path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        file.sync_all()?;
+        fs::rename(&temporary, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    result
+}
+
+/// Unified diff of the lines of `old` and `new`, both labelled `path`. Empty when they are
+/// equal.
+pub fn unified_diff(path: &Path, old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.split_inclusive('\n').collect();
+    let new: Vec<&str> = new.split_inclusive('\n').collect();
+    let prefix = old.iter().zip(&new).take_while(|(o, n)| o == n).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(o, n)| o == n)
+        .count();
+    let mut ops: Vec<(char, &str)> = old[..prefix].iter().map(|l| (' ', *l)).collect();
+    ops.extend(diff_lines(
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    ));
+    ops.extend(old[old.len() - suffix..].iter().map(|l| (' ', *l)));
+    let changes: Vec<usize> = (0..ops.len()).filter(|i| ops[*i].0 != ' ').collect();
+    if changes.is_empty() {
+        return String::new();
+    }
+    let mut diff = format!("--- {0}\n+++ {0}\n", path.display());
+    let mut i = 0;
+    while i < changes.len() {
+        let start = changes[i].saturating_sub(CONTEXT);
+        while i + 1 < changes.len() && changes[i + 1] - changes[i] <= 2 * CONTEXT + 1 {
+            i += 1;
+        }
+        let end = (changes[i] + 1 + CONTEXT).min(ops.len());
+        i += 1;
+        let count = |ops: &[(char, &str)], skip| ops.iter().filter(|(op, _)| *op != skip).count();
+        let (old_start, new_start) = (count(&ops[..start], '+'), count(&ops[..start], '-'));
+        let hunk = &ops[start..end];
+        diff.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(old_start, count(hunk, '+')),
+            hunk_range(new_start, count(hunk, '-'))
+        ));
+        for (op, line) in hunk {
+            diff.push(*op);
+            diff.push_str(line);
+            if !line.ends_with('\n') {
+                diff.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+    }
+    diff
+}
+
+/// Range of a hunk, as "start,count" with lines starting at 1.
+fn hunk_range(before: usize, count: usize) -> String {
+    match count {
+        0 => format!("{before},0"),
+        1 => format!("{}", before + 1),
+        _ => format!("{},{count}", before + 1),
+    }
+}
+
+/// Shortest edit script from `old` to `new`, with Myers' algorithm: lines kept (' '), removed
+/// ('-') and added ('+').
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<(char, &'a str)> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let offset = n + m;
+    let mut v = vec![0isize; 2 * offset as usize + 2];
+    let index = |k: isize| (k + offset) as usize;
+    let mut trace = vec![];
+    'search: for d in 0..=offset {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let mut x = match k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) {
+                true => v[index(k + 1)],
+                false => v[index(k - 1)] + 1,
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[index(k)] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+    let mut ops = vec![];
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let previous = match k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) {
+            true => k + 1,
+            false => k - 1,
+        };
+        let previous_x = v[index(previous)];
+        let previous_y = previous_x - previous;
+        while x > previous_x && y > previous_y {
+            x -= 1;
+            y -= 1;
+            ops.push((' ', old[x as usize]));
+        }
+        if d > 0 && x == previous_x {
+            y -= 1;
+            ops.push(('+', new[y as usize]));
+        } else if d > 0 {
+            x -= 1;
+            ops.push(('-', old[x as usize]));
+        }
+    }
+    ops.reverse();
+    ops
+}
+
+/// 64 bits FNV-1a hash of bytes. Unlike the hasher of the standard library, it never changes,
+/// so it is stable across runs, platforms and versions, e.g. for the UIDs of iCalendar exports.
+pub fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+impl fmt::Display for WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WriteError::Io(path, e) => write!(f, "cannot write {}: {e}", path.display()),
+            WriteError::Locked(path, owner) => write!(
+                f,
+                "{} has unsaved changes in emacs ({owner}), save it first",
+                path.display()
+            ),
+            WriteError::Changed(path) => write!(
+                f,
+                "{} changed since it was read, run the command again",
+                path.display()
+            ),
+        }
+    }
+}
+
+impl Error for WriteError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, lock_owner, read, unified_diff, write, LockPolicy, Origin, WriteError};
+    use std::path::Path;
+
+    #[test]
+    fn diffs() {
+        let old = "* A\n1\n2\n3\n4\n5\n6\n7\n8\n9\n* B";
+        let new = "* A\nnew\n1\n2\n3\n4\n5\n6\n7\n8\n* B\n";
+        assert_eq!(
+            unified_diff(Path::new("a.org"), old, new),
+            "--- a.org\n+++ a.org\n@@ -1,4 +1,5 @@\n * A\n+new\n 1\n 2\n 3\n@@ -7,5 +8,4 @@\n 6\n 7\n 8\n-9\n-* B\n\\ No newline at end of file\n+* B\n"
+        );
+        assert_eq!(unified_diff(Path::new("a.org"), old, old), "");
+        assert_eq!(
+            unified_diff(Path::new("a.org"), "", "* A\n"),
+            "--- a.org\n+++ a.org\n@@ -0,0 +1 @@\n+* A\n"
+        );
+    }
+
+    #[test]
+    fn safe_writes() {
+        let dir = std::env::temp_dir().join(format!("orgparser-write-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("a.org");
+        std::fs::write(&path, "* A\n").unwrap();
+        let (_, origin) = read(&path).unwrap();
+        write(&path, "* B", Some(&origin), LockPolicy::Refuse).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "* B\n");
+        assert!(matches!(
+            write(&path, "* C", Some(&origin), LockPolicy::Refuse),
+            Err(WriteError::Changed(_))
+        ));
+        assert!(check(&path, Some(&origin), LockPolicy::Ignore).is_err());
+        let missing = Origin::new(None, "");
+        assert!(missing.is_current(&dir.join("new.org")).unwrap());
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink("me@host.42:1700000000", dir.join(".#a.org")).unwrap();
+            assert_eq!(lock_owner(&path).as_deref(), Some("me@host.42"));
+            let (_, origin) = read(&path).unwrap();
+            assert!(matches!(
+                write(&path, "* C\n", Some(&origin), LockPolicy::Refuse),
+                Err(WriteError::Locked(..))
+            ));
+            write(&path, "* C\n", Some(&origin), LockPolicy::Ignore).unwrap();
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
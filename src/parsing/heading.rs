@@ -0,0 +1,171 @@
+//! Grammar of a single org heading line.
+//! A heading is made of: STARS KEYWORD PRIORITY TITLE TAGS, where only the stars are mandatory.
+//! Example: "** TODO [#A] Write report :work:urgent:"
+
+use std::fmt;
+
+/// Keywords recognized when no other keyword is configured.
+pub const DEFAULT_KEYWORDS: [&str; 2] = ["TODO", "DONE"];
+
+/// A parsed heading line.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Heading {
+    /// Outline level, i.e. the number of stars
+    pub level: usize,
+    /// TODO keyword, if the first word of the heading is one
+    pub keyword: Option<String>,
+    /// Priority cookie. Example: 'A' for "[#A]"
+    pub priority: Option<char>,
+    /// Text of the heading, without keyword, priority and tags
+    pub title: String,
+    /// Tags at the end of the heading, without the colons
+    pub tags: Vec<String>,
+}
+
+impl Heading {
+    /// Parse a heading line using the default keywords.
+    /// Returns None if the line is not a heading.
+    pub fn parse(line: &str) -> Option<Heading> {
+        Self::parse_with_keywords(line, &DEFAULT_KEYWORDS)
+    }
+    /// Parse a heading line, using `keywords` as the list of TODO keywords.
+    pub fn parse_with_keywords<S: AsRef<str>>(line: &str, keywords: &[S]) -> Option<Heading> {
+        if !super::is_heading(line) {
+            return None;
+        }
+        let rest = line.trim_start_matches('*');
+        let level = line.len() - rest.len();
+        let rest = rest.trim();
+
+        let (keyword, rest) = split_keyword(rest, keywords);
+        let (priority, rest) = split_priority(rest);
+        let (title, tags) = split_tags(rest);
+        Some(Heading {
+            level,
+            keyword: keyword.map(String::from),
+            priority,
+            title: String::from(title),
+            tags: tags.into_iter().map(String::from).collect(),
+        })
+    }
+}
+
+impl fmt::Display for Heading {
+    /// Write the heading line. Example: "** TODO [#A] Write report :work:urgent:"
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", "*".repeat(self.level.max(1)))?;
+        if let Some(keyword) = &self.keyword {
+            write!(f, " {keyword}")?;
+        }
+        if let Some(priority) = self.priority {
+            write!(f, " [#{priority}]")?;
+        }
+        if !self.title.is_empty() {
+            write!(f, " {}", self.title)?;
+        }
+        if !self.tags.is_empty() {
+            write!(f, " :{}:", self.tags.join(":"))?;
+        }
+        Ok(())
+    }
+}
+
+/// Split the TODO keyword from the rest of the heading.
+/// The keyword must be followed by a blank space or by the end of the line.
+fn split_keyword<'a, S: AsRef<str>>(text: &'a str, keywords: &[S]) -> (Option<&'a str>, &'a str) {
+    let word = text.split(char::is_whitespace).next().unwrap_or("");
+    if keywords.iter().any(|k| k.as_ref() == word) {
+        return (Some(word), text[word.len()..].trim_start());
+    }
+    // "*TODO:" was accepted by the first version of the parser
+    if let Some(word) = word.strip_suffix(':') {
+        if keywords.iter().any(|k| k.as_ref() == word) {
+            return (Some(word), text[word.len() + 1..].trim_start());
+        }
+    }
+    (None, text)
+}
+
+/// Split the priority cookie (e.g. "[#A]") from the rest of the heading.
+fn split_priority(text: &str) -> (Option<char>, &str) {
+    let mut chars = text.chars();
+    match (chars.next(), chars.next(), chars.next(), chars.next()) {
+        (Some('['), Some('#'), Some(p), Some(']')) if p.is_ascii_alphanumeric() => {
+            (Some(p), text[4..].trim_start())
+        }
+        _ => (None, text),
+    }
+}
+
+/// Split the tags (e.g. ":work:urgent:") from the end of the heading.
+/// Tags are made of letters, numbers, '_', '@', '#' and '%' and must be preceded by a blank space.
+fn split_tags(text: &str) -> (&str, Vec<&str>) {
+    let (title, last) = match text.rfind(char::is_whitespace) {
+        Some(i) => (&text[..i], &text[i + 1..]),
+        None => ("", text),
+    };
+    let is_tag_char = |c: char| c.is_alphanumeric() || "_@#%".contains(c);
+    let valid = last.len() > 2
+        && last.starts_with(':')
+        && last.ends_with(':')
+        && last[1..last.len() - 1]
+            .split(':')
+            .all(|t| !t.is_empty() && t.chars().all(is_tag_char));
+    if !valid {
+        return (text, vec![]);
+    }
+    let tags = last[1..last.len() - 1].split(':').collect();
+    (title.trim_end(), tags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Heading;
+
+    #[test]
+    fn full_heading() {
+        let heading = Heading::parse("** TODO [#A] Write report :work:urgent:").unwrap();
+        assert_eq!(heading.level, 2);
+        assert_eq!(heading.keyword.as_deref(), Some("TODO"));
+        assert_eq!(heading.priority, Some('A'));
+        assert_eq!(heading.title, "Write report");
+        assert_eq!(heading.tags, vec!["work", "urgent"]);
+        assert_eq!(
+            heading.to_string(),
+            "** TODO [#A] Write report :work:urgent:"
+        );
+    }
+
+    #[test]
+    fn optional_parts() {
+        let heading = Heading::parse("* Projects").unwrap();
+        assert_eq!(heading.level, 1);
+        assert_eq!(heading.keyword, None);
+        assert_eq!(heading.priority, None);
+        assert_eq!(heading.title, "Projects");
+        assert!(heading.tags.is_empty());
+
+        let heading = Heading::parse("*** DONE").unwrap();
+        assert_eq!(heading.keyword.as_deref(), Some("DONE"));
+        assert_eq!(heading.title, "");
+
+        let heading = Heading::parse("* TODOS are not keywords :not:a tag:").unwrap();
+        assert_eq!(heading.keyword, None);
+        assert_eq!(heading.title, "TODOS are not keywords :not:a tag:");
+
+        let heading = Heading::parse("* :tag:").unwrap();
+        assert_eq!(heading.title, "");
+        assert_eq!(heading.tags, vec!["tag"]);
+
+        assert_eq!(Heading::parse(" * list item"), None);
+    }
+
+    #[test]
+    fn legacy_heading() {
+        let heading = Heading::parse("*TODO: ranger ma chambre").unwrap();
+        assert_eq!(heading.level, 1);
+        assert_eq!(heading.keyword.as_deref(), Some("TODO"));
+        assert_eq!(heading.title, "ranger ma chambre");
+    }
+}
@@ -0,0 +1,179 @@
+//! TODO keyword sequences, as defined by "#+TODO:", "#+SEQ_TODO:" and "#+TYP_TODO:" lines.
+//! Example: "#+TODO: TODO(t) NEXT WAITING(w@/!) | DONE(d) CANCELLED"
+//! Keywords on the left of "|" are active states, keywords on the right are done states.
+//! Without "|", the last keyword is the only done state.
+
+/// In-buffer settings defining keyword sequences, in lowercase.
+const KEYWORD_SETTINGS: [&str; 3] = ["#+todo:", "#+seq_todo:", "#+typ_todo:"];
+
+/// State of a heading according to its keyword.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum KeywordState {
+    /// The task still has to be done. Example: TODO, NEXT, WAITING
+    Active,
+    /// The task is finished. Example: DONE, CANCELLED
+    Done,
+}
+
+/// A single TODO keyword with its fast-access key. Example: "TODO(t)"
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Keyword {
+    pub name: String,
+    pub key: Option<char>,
+}
+
+/// A sequence of keywords, i.e. the content of a single "#+TODO:" line.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KeywordSequence {
+    pub active: Vec<Keyword>,
+    pub done: Vec<Keyword>,
+}
+
+/// All the keyword sequences that apply to a file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TodoKeywords {
+    pub sequences: Vec<KeywordSequence>,
+}
+
+impl Keyword {
+    /// Parse a keyword with an optional fast-access key and logging options. Example: "WAIT(w@/!)"
+    fn parse(word: &str) -> Keyword {
+        match word.split_once('(') {
+            Some((name, options)) if word.ends_with(')') => Keyword {
+                name: String::from(name),
+                key: options.chars().next().filter(|c| c.is_alphanumeric()),
+            },
+            _ => Keyword {
+                name: String::from(word),
+                key: None,
+            },
+        }
+    }
+}
+
+impl KeywordSequence {
+    /// Parse the value of a "#+TODO:" line. Example: "TODO NEXT | DONE CANCELLED"
+    /// Returns None if there is no keyword at all.
+    pub fn parse(value: &str) -> Option<KeywordSequence> {
+        let words: Vec<&str> = value.split_whitespace().collect();
+        let (active, done) = match words.iter().position(|w| *w == "|") {
+            Some(i) => (&words[..i], &words[i + 1..]),
+            None if words.is_empty() => return None,
+            None if words.len() == 1 => (&words[..], &words[..0]),
+            None => words.split_at(words.len() - 1),
+        };
+        Some(KeywordSequence {
+            active: active.iter().map(|w| Keyword::parse(w)).collect(),
+            done: done.iter().map(|w| Keyword::parse(w)).collect(),
+        })
+    }
+}
+
+impl Default for TodoKeywords {
+    /// The org default: "TODO | DONE"
+    fn default() -> Self {
+        TodoKeywords {
+            sequences: vec![KeywordSequence::parse("TODO | DONE").unwrap()],
+        }
+    }
+}
+
+impl TodoKeywords {
+    /// Read the keyword sequences of a file.
+    /// When the file does not define any sequence, `default` is used instead.
+    pub fn from_file(file: &str, default: &TodoKeywords) -> TodoKeywords {
+        let sequences: Vec<KeywordSequence> = file
+            .lines()
+            .filter_map(Self::setting_value)
+            .filter_map(KeywordSequence::parse)
+            .collect();
+        if sequences.is_empty() {
+            default.clone()
+        } else {
+            TodoKeywords { sequences }
+        }
+    }
+    /// Return the value of a keyword setting line, if the line is one.
+    fn setting_value(line: &str) -> Option<&str> {
+        let line = line.trim_start();
+        KEYWORD_SETTINGS.iter().find_map(|setting| {
+            let prefix = line.get(..setting.len())?;
+            prefix
+                .eq_ignore_ascii_case(setting)
+                .then(|| &line[setting.len()..])
+        })
+    }
+    /// Names of all keywords, active and done.
+    pub fn names(&self) -> Vec<&str> {
+        self.sequences
+            .iter()
+            .flat_map(|s| s.active.iter().chain(s.done.iter()))
+            .map(|k| k.name.as_str())
+            .collect()
+    }
+    /// Classify a keyword as active or done.
+    /// Returns None if the word is not a known keyword.
+    pub fn state(&self, keyword: &str) -> Option<KeywordState> {
+        self.sequences.iter().find_map(|s| {
+            if s.active.iter().any(|k| k.name == keyword) {
+                Some(KeywordState::Active)
+            } else if s.done.iter().any(|k| k.name == keyword) {
+                Some(KeywordState::Done)
+            } else {
+                None
+            }
+        })
+    }
+    /// Find the keyword bound to a fast-access key.
+    pub fn by_key(&self, key: char) -> Option<&Keyword> {
+        self.sequences
+            .iter()
+            .flat_map(|s| s.active.iter().chain(s.done.iter()))
+            .find(|k| k.key == Some(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KeywordSequence, KeywordState, TodoKeywords};
+
+    #[test]
+    fn sequence_with_separator() {
+        let seq = KeywordSequence::parse("TODO(t) NEXT WAITING(w@/!) | DONE(d) CANCELLED").unwrap();
+        let active: Vec<&str> = seq.active.iter().map(|k| k.name.as_str()).collect();
+        let done: Vec<&str> = seq.done.iter().map(|k| k.name.as_str()).collect();
+        assert_eq!(active, ["TODO", "NEXT", "WAITING"]);
+        assert_eq!(done, ["DONE", "CANCELLED"]);
+        assert_eq!(seq.active[0].key, Some('t'));
+        assert_eq!(seq.active[1].key, None);
+        assert_eq!(seq.active[2].key, Some('w'));
+    }
+
+    #[test]
+    fn sequence_without_separator() {
+        let seq = KeywordSequence::parse("TODO NEXT DONE").unwrap();
+        assert_eq!(seq.active.len(), 2);
+        assert_eq!(seq.done[0].name, "DONE");
+        assert_eq!(KeywordSequence::parse("  "), None);
+    }
+
+    #[test]
+    fn keywords_from_file() {
+        let file =
+            "#+title: x\n#+TODO: TODO NEXT | DONE\n#+seq_todo: REPORT BUG | FIXED\n* NEXT a\n";
+        let keywords = TodoKeywords::from_file(file, &TodoKeywords::default());
+        assert_eq!(keywords.sequences.len(), 2);
+        assert_eq!(keywords.state("NEXT"), Some(KeywordState::Active));
+        assert_eq!(keywords.state("FIXED"), Some(KeywordState::Done));
+        assert_eq!(keywords.state("WAITING"), None);
+
+        let keywords = TodoKeywords::from_file("* TODO a\n", &TodoKeywords::default());
+        assert_eq!(keywords, TodoKeywords::default());
+        assert_eq!(keywords.names(), ["TODO", "DONE"]);
+    }
+}
@@ -0,0 +1,201 @@
+//! Clock entries and notes of a heading, usually inside its ":LOGBOOK:" drawer. Example:
+//!
+//! ```org
+//! :LOGBOOK:
+//! CLOCK: [2023-09-05 Tue 14:00]
+//! CLOCK: [2023-09-05 Tue 10:00]--[2023-09-05 Tue 11:30] =>  1:30
+//! - State "DONE"       from "TODO"       [2023-09-05 Tue 11:30] \\
+//!   Deployed on the new server
+//! - Note taken on [2023-09-04 Mon 09:12] \\
+//!   Ask for the credentials
+//! :END:
+//! ```
+//!
+//! The first clock is still running. Entries written outside of a drawer are read too.
+use super::timestamp::Timestamp;
+use chrono::{Duration, NaiveDateTime};
+
+/// A clocked time interval. `end` is None while the clock is running.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Clock {
+    pub start: NaiveDateTime,
+    pub end: Option<NaiveDateTime>,
+}
+
+/// A change of the keyword of a heading. `from` is None when the heading had no keyword.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StateChange {
+    pub from: Option<String>,
+    pub to: String,
+}
+
+/// A note of the logbook, either written by hand or when the keyword changed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Note {
+    pub state: Option<StateChange>,
+    pub at: NaiveDateTime,
+    /// Text of the note, without indentation
+    pub text: String,
+}
+
+/// The clock entries and the notes of a heading, most recent first as org writes them.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Logbook {
+    pub clocks: Vec<Clock>,
+    pub notes: Vec<Note>,
+}
+
+impl Clock {
+    /// Parse a "CLOCK:" line. Returns None if the line is not a valid clock entry.
+    pub fn parse(line: &str) -> Option<Clock> {
+        let rest = line.trim_start().strip_prefix("CLOCK:")?.trim_start();
+        let (timestamp, _) = Timestamp::parse(rest).ok()?;
+        Some(Clock {
+            start: timestamp.start.datetime(),
+            end: timestamp.end.map(|end| end.datetime()),
+        })
+    }
+    /// Verify if the clock is still running.
+    pub fn is_running(&self) -> bool {
+        self.end.is_none()
+    }
+    /// Clocked time between `from` and `to`. A running clock runs until `now`.
+    pub fn duration_between(
+        &self,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+        now: NaiveDateTime,
+    ) -> Duration {
+        let start = self.start.max(from);
+        let end = self.end.unwrap_or(now).min(to);
+        (end - start).max(Duration::zero())
+    }
+}
+
+impl Logbook {
+    /// Read the clock entries and the notes of the section of a heading.
+    pub fn parse(section: &str) -> Logbook {
+        let mut logbook = Logbook::default();
+        let mut lines = section.lines().peekable();
+        while let Some(line) = lines.next() {
+            if let Some(clock) = Clock::parse(line) {
+                logbook.clocks.push(clock);
+                continue;
+            }
+            let Some((state, at)) = note_header(line) else {
+                continue;
+            };
+            // The text of the note is indented below its header
+            let indent = line.len() - line.trim_start().len();
+            let mut text = vec![];
+            while let Some(next) = lines.next_if(|l| {
+                !l.trim().is_empty() && l.len() - l.trim_start().len() > indent && !is_item(l)
+            }) {
+                text.push(next.trim());
+            }
+            logbook.notes.push(Note {
+                state,
+                at,
+                text: text.join("\n"),
+            });
+        }
+        logbook
+    }
+    /// Verify if a clock of the logbook is still running.
+    pub fn is_clocked_in(&self) -> bool {
+        self.clocks.iter().any(Clock::is_running)
+    }
+}
+
+/// Verify if a line is a list item.
+fn is_item(line: &str) -> bool {
+    line.trim_start().starts_with("- ")
+}
+
+/// Parse the first line of a note:
+/// "- State "DONE" from "TODO" [2023-09-05 Tue 11:30] \\" or
+/// "- Note taken on [2023-09-04 Mon 09:12] \\"
+fn note_header(line: &str) -> Option<(Option<StateChange>, NaiveDateTime)> {
+    let item = line.trim_start().strip_prefix("- ")?.trim_start();
+    let (state, rest) = if let Some(rest) = item.strip_prefix("State ") {
+        let (to, rest) = quoted(rest)?;
+        let rest = rest.trim_start();
+        let rest = rest.strip_prefix("from ").unwrap_or(rest);
+        let (from, rest) = match quoted(rest) {
+            Some((from, rest)) => (Some(from).filter(|f| !f.is_empty()), rest),
+            None => (None, rest),
+        };
+        (Some(StateChange { from, to }), rest)
+    } else {
+        (None, item.strip_prefix("Note taken on ")?)
+    };
+    let (timestamp, _) = Timestamp::parse(rest.trim_start()).ok()?;
+    Some((state, timestamp.start.datetime()))
+}
+
+/// Parse a double-quoted word at the start of `text`, and return it with the text following it.
+fn quoted(text: &str) -> Option<(String, &str)> {
+    let rest = text.trim_start().strip_prefix('"')?;
+    let (word, rest) = rest.split_once('"')?;
+    Some((String::from(word), rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Clock, Logbook};
+    use chrono::{Duration, NaiveDate, NaiveDateTime};
+
+    fn at(d: u32, h: u32, m: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2023, 9, d)
+            .unwrap()
+            .and_hms_opt(h, m, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn logbook_entries() {
+        let section = "* DONE Deploy\nCLOSED: [2023-09-05 Tue 11:30]\n:LOGBOOK:\nCLOCK: [2023-09-05 Tue 14:00]\nCLOCK: [2023-09-05 Tue 10:00]--[2023-09-05 Tue 11:30] =>  1:30\n- State \"DONE\"       from \"TODO\"       [2023-09-05 Tue 11:30] \\\\\n  Deployed on the new server\n  at last\n- Note taken on [2023-09-04 Mon 09:12] \\\\\n  Ask for the credentials\n- State \"TODO\"       from              [2023-09-01 Fri 08:00]\n:END:\n";
+        let logbook = Logbook::parse(section);
+        assert_eq!(logbook.clocks.len(), 2);
+        assert!(logbook.is_clocked_in());
+        assert_eq!(logbook.clocks[1].end, Some(at(5, 11, 30)));
+        assert_eq!(logbook.notes.len(), 3);
+        let done = &logbook.notes[0];
+        let state = done.state.as_ref().unwrap();
+        assert_eq!(state.from.as_deref(), Some("TODO"));
+        assert_eq!(state.to, "DONE");
+        assert_eq!(done.at, at(5, 11, 30));
+        assert_eq!(done.text, "Deployed on the new server\nat last");
+        assert_eq!(logbook.notes[1].state, None);
+        assert_eq!(logbook.notes[1].text, "Ask for the credentials");
+        let state = logbook.notes[2].state.as_ref().unwrap();
+        assert_eq!((state.from.as_deref(), state.to.as_str()), (None, "TODO"));
+    }
+
+    #[test]
+    fn clock_durations() {
+        let clock =
+            Clock::parse("CLOCK: [2023-09-04 Mon 23:00]--[2023-09-05 Tue 01:30] =>  2:30").unwrap();
+        let day = |d| (at(d, 0, 0), at(d + 1, 0, 0));
+        let (from, to) = day(5);
+        assert_eq!(
+            clock.duration_between(from, to, at(6, 0, 0)),
+            Duration::minutes(90)
+        );
+        let (from, to) = day(6);
+        assert_eq!(
+            clock.duration_between(from, to, at(6, 0, 0)),
+            Duration::zero()
+        );
+        let running = Clock::parse("CLOCK: [2023-09-05 Tue 10:00]").unwrap();
+        assert_eq!(
+            running.duration_between(from - Duration::days(1), to, at(5, 10, 45)),
+            Duration::minutes(45)
+        );
+        assert!(Clock::parse("CLOCK: soon").is_none());
+    }
+}
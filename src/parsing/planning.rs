@@ -0,0 +1,104 @@
+//! Planning line of a heading. Example: "DEADLINE: <2023-09-07 Thu> SCHEDULED: <2023-09-05 Tue>"
+use super::timestamp::{Timestamp, TimestampError};
+use std::fmt;
+
+/// Keywords allowed at the start of a planning line.
+pub const PLANNING_KEYWORDS: [&str; 3] = ["SCHEDULED:", "DEADLINE:", "CLOSED:"];
+
+/// Timestamps of a planning line.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Planning {
+    pub scheduled: Option<Timestamp>,
+    pub deadline: Option<Timestamp>,
+    pub closed: Option<Timestamp>,
+}
+
+impl Planning {
+    /// Parse the timestamps following "SCHEDULED:", "DEADLINE:" and "CLOSED:" in a line.
+    /// A keyword followed by an invalid timestamp is ignored.
+    pub fn parse(line: &str) -> Planning {
+        Self::parse_with_errors(line).0
+    }
+    /// Parse a planning line, and return the errors of the keywords followed by an invalid
+    /// timestamp. The columns of the errors are byte offsets inside the line.
+    pub fn parse_with_errors(line: &str) -> (Planning, Vec<TimestampError>) {
+        let mut errors = vec![];
+        let mut timestamp_after = |keyword: &str| {
+            let (before, rest) = line.split_once(keyword)?;
+            let trimmed = rest.trim_start();
+            let offset = before.len() + keyword.len() + rest.len() - trimmed.len();
+            match Timestamp::parse(trimmed) {
+                Ok((timestamp, _)) => Some(timestamp),
+                Err(mut e) => {
+                    e.column += offset;
+                    errors.push(e);
+                    None
+                }
+            }
+        };
+        let planning = Planning {
+            scheduled: timestamp_after("SCHEDULED:"),
+            deadline: timestamp_after("DEADLINE:"),
+            closed: timestamp_after("CLOSED:"),
+        };
+        (planning, errors)
+    }
+    /// Verify if no timestamp was found.
+    pub fn is_empty(&self) -> bool {
+        self.scheduled.is_none() && self.deadline.is_none() && self.closed.is_none()
+    }
+}
+
+impl fmt::Display for Planning {
+    /// Write the planning line, in the order of org: CLOSED, DEADLINE then SCHEDULED
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let timestamps = [
+            ("CLOSED:", &self.closed),
+            ("DEADLINE:", &self.deadline),
+            ("SCHEDULED:", &self.scheduled),
+        ];
+        let mut separator = "";
+        for (keyword, timestamp) in timestamps {
+            if let Some(timestamp) = timestamp {
+                write!(f, "{separator}{keyword} {timestamp}")?;
+                separator = " ";
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Planning;
+
+    #[test]
+    fn planning_line() {
+        let planning =
+            Planning::parse("  DEADLINE: <2023-09-07 Thu -2d> SCHEDULED: <2023-09-05 Tue 10:00>");
+        assert_eq!(
+            planning.to_string(),
+            "DEADLINE: <2023-09-07 Thu -2d> SCHEDULED: <2023-09-05 Tue 10:00>"
+        );
+        assert_eq!(
+            planning.scheduled.unwrap().to_string(),
+            "<2023-09-05 Tue 10:00>"
+        );
+        assert_eq!(
+            planning.deadline.unwrap().to_string(),
+            "<2023-09-07 Thu -2d>"
+        );
+        assert!(planning.closed.is_none());
+        assert!(Planning::parse("SCHEDULED: <blabla>").is_empty());
+    }
+
+    #[test]
+    fn planning_errors() {
+        let (planning, errors) =
+            Planning::parse_with_errors("  SCHEDULED: <2023-09-05 Tue> DEADLINE:  <2023-13-01>");
+        assert!(planning.scheduled.is_some());
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].column, 42);
+    }
+}
@@ -0,0 +1,134 @@
+//! Properties of the headings, from ":PROPERTIES:" drawers and "#+PROPERTY:" lines.
+//! Example: "#+PROPERTY: Effort_ALL 0:10 0:30 1:00" or ":LOCATION: Office" in a drawer.
+//! Property names are case-insensitive and are kept in uppercase. A name ending with "+" adds
+//! its value to the current one: ":VAR+: b" after ":VAR: a" gives "a b".
+use std::collections::BTreeMap;
+
+/// In-buffer setting defining a property for the whole file, in lowercase.
+const PROPERTY_SETTING: &str = "#+property:";
+/// In-buffer setting defining the category of the file, in lowercase.
+const CATEGORY_SETTING: &str = "#+category:";
+
+/// Property holding the category of a heading, shown in the agenda.
+pub const CATEGORY: &str = "CATEGORY";
+
+/// Suffix of the properties listing the allowed values of another property.
+pub const ALLOWED_VALUES_SUFFIX: &str = "_ALL";
+
+/// A set of properties, by uppercase name.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Properties {
+    map: BTreeMap<String, String>,
+}
+
+impl Properties {
+    /// Read the "#+PROPERTY: NAME VALUE" lines of a file. A "#+CATEGORY:" line sets the
+    /// CATEGORY property.
+    pub fn from_file(file: &str) -> Properties {
+        let mut properties = Properties::default();
+        for line in file.lines() {
+            if let Some(value) = setting_value(line, PROPERTY_SETTING) {
+                let (name, value) = value.trim().split_once(' ').unwrap_or((value.trim(), ""));
+                if !name.is_empty() {
+                    properties.set(name, value);
+                }
+            } else if let Some(category) = setting_value(line, CATEGORY_SETTING) {
+                properties.set(CATEGORY, category);
+            }
+        }
+        properties
+    }
+    /// Value of a property. Names are case-insensitive.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.map.get(&name.to_ascii_uppercase()).map(String::as_str)
+    }
+    /// Set a property, or add to its value if `name` ends with "+".
+    pub fn set(&mut self, name: &str, value: &str) {
+        let value = value.trim();
+        match name.strip_suffix('+') {
+            Some(name) => {
+                let current = self.map.entry(name.to_ascii_uppercase()).or_default();
+                if !current.is_empty() && !value.is_empty() {
+                    current.push(' ');
+                }
+                current.push_str(value);
+            }
+            None => {
+                self.map
+                    .insert(name.to_ascii_uppercase(), String::from(value));
+            }
+        }
+    }
+    /// Set every property of a drawer, in order.
+    pub fn extend<'a, I>(&mut self, drawer: I)
+    where
+        I: IntoIterator<Item = &'a (String, String)>,
+    {
+        for (name, value) in drawer {
+            self.set(name, value);
+        }
+    }
+    /// Values allowed for a property, as listed by its "_ALL" property. Empty if any value is
+    /// allowed.
+    pub fn allowed_values(&self, name: &str) -> Vec<&str> {
+        self.get(&format!("{name}{ALLOWED_VALUES_SUFFIX}"))
+            .map(|values| values.split_whitespace().collect())
+            .unwrap_or_default()
+    }
+    /// The properties as a map from uppercase names to values.
+    pub fn as_map(&self) -> &BTreeMap<String, String> {
+        &self.map
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.map.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+/// Return the value of an in-buffer setting line, if the line is one.
+fn setting_value<'a>(line: &'a str, setting: &str) -> Option<&'a str> {
+    let line = line.trim_start();
+    let prefix = line.get(..setting.len())?;
+    prefix
+        .eq_ignore_ascii_case(setting)
+        .then(|| &line[setting.len()..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Properties;
+
+    #[test]
+    fn file_properties() {
+        let file = "#+TITLE: Tasks\n#+PROPERTY: Effort_ALL 0:10 0:30 1:00\n#+property: header-args :results silent\n#+PROPERTY: header-args+ :exports code\n#+CATEGORY: tasks\n";
+        let properties = Properties::from_file(file);
+        assert_eq!(properties.len(), 3);
+        assert_eq!(properties.get("category"), Some("tasks"));
+        assert_eq!(
+            properties.allowed_values("effort"),
+            ["0:10", "0:30", "1:00"]
+        );
+        assert_eq!(
+            properties.get("HEADER-ARGS"),
+            Some(":results silent :exports code")
+        );
+        assert!(properties.allowed_values("LOCATION").is_empty());
+    }
+
+    #[test]
+    fn accumulated_values() {
+        let mut properties = Properties::default();
+        properties.set("var+", "a");
+        properties.set("Var+", "b");
+        assert_eq!(properties.get("VAR"), Some("a b"));
+        properties.set("var", "c");
+        assert_eq!(properties.get("var"), Some("c"));
+    }
+}
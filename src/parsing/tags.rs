@@ -0,0 +1,135 @@
+//! File tags and tag groups, as defined by "#+FILETAGS:" and "#+TAGS:" lines.
+//! Example: "#+TAGS: { @home(h) @office(o) } laptop [ Work : meeting report ]"
+//! A group written "[ Work : meeting report ]" or "{ Context : @home @office }" has a group tag:
+//! searching for "Work" also finds the headings tagged "meeting" or "report". Groups can be
+//! nested, a member of a group being the group tag of another one.
+
+/// In-buffer setting defining the tags of the whole file, in lowercase.
+const FILETAGS_SETTING: &str = "#+filetags:";
+/// In-buffer setting defining the tags and tag groups of the file, in lowercase.
+const TAGS_SETTING: &str = "#+tags:";
+
+/// A group of tags. Example: "[ Work : meeting report ]"
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TagGroup {
+    /// Group tag, matching every member of the group
+    pub name: Option<String>,
+    pub members: Vec<String>,
+    /// Only one tag of the group can be set on a heading, for groups written with braces
+    pub exclusive: bool,
+}
+
+/// All the tag groups of a file.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TagGroups {
+    pub groups: Vec<TagGroup>,
+}
+
+/// Read the tags of the "#+FILETAGS:" lines of a file. Example: "#+FILETAGS: :work:project:"
+pub fn file_tags(file: &str) -> Vec<String> {
+    let mut tags: Vec<String> = vec![];
+    let words = file
+        .lines()
+        .filter_map(|l| setting_value(l, FILETAGS_SETTING))
+        .flat_map(|value| value.split(|c: char| c == ':' || c.is_whitespace()));
+    for tag in words.filter(|t| !t.is_empty()) {
+        if !tags.iter().any(|t| t == tag) {
+            tags.push(String::from(tag));
+        }
+    }
+    tags
+}
+
+impl TagGroups {
+    /// Read the tag groups of the "#+TAGS:" lines of a file.
+    pub fn from_file(file: &str) -> TagGroups {
+        let mut groups = vec![];
+        for value in file.lines().filter_map(|l| setting_value(l, TAGS_SETTING)) {
+            let mut group: Option<TagGroup> = None;
+            for word in value.split_whitespace() {
+                match (word, group.as_mut()) {
+                    ("{" | "[", _) => {
+                        group = Some(TagGroup {
+                            name: None,
+                            members: vec![],
+                            exclusive: word == "{",
+                        })
+                    }
+                    ("}" | "]", Some(_)) => groups.extend(group.take()),
+                    (":", Some(g)) if g.name.is_none() && g.members.len() == 1 => {
+                        g.name = g.members.pop();
+                    }
+                    (tag, Some(g)) => g.members.push(tag_name(tag)),
+                    _ => {}
+                }
+            }
+        }
+        TagGroups { groups }
+    }
+    /// The group tags matching `tag`: the ones of the groups it belongs to, directly or through
+    /// nested groups.
+    pub fn group_tags(&self, tag: &str) -> Vec<&str> {
+        let mut found: Vec<&str> = vec![];
+        let mut pending = vec![tag];
+        while let Some(tag) = pending.pop() {
+            let names = self
+                .groups
+                .iter()
+                .filter(|g| g.members.iter().any(|m| m == tag))
+                .filter_map(|g| g.name.as_deref());
+            for name in names {
+                if name != tag && !found.contains(&name) {
+                    found.push(name);
+                    pending.push(name);
+                }
+            }
+        }
+        found
+    }
+}
+
+/// Remove the fast-access key of a tag. Example: "@home(h)" gives "@home"
+fn tag_name(word: &str) -> String {
+    match word.split_once('(') {
+        Some((name, _)) if word.ends_with(')') => String::from(name),
+        _ => String::from(word),
+    }
+}
+
+/// Return the value of an in-buffer setting line, if the line is one.
+fn setting_value<'a>(line: &'a str, setting: &str) -> Option<&'a str> {
+    let line = line.trim_start();
+    let prefix = line.get(..setting.len())?;
+    prefix
+        .eq_ignore_ascii_case(setting)
+        .then(|| &line[setting.len()..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{file_tags, TagGroups};
+
+    #[test]
+    fn filetags() {
+        let file = "#+FILETAGS: :work:project:\n#+filetags: home work\n* Heading :work:\n";
+        assert_eq!(file_tags(file), ["work", "project", "home"]);
+        assert!(file_tags("* Heading").is_empty());
+    }
+
+    #[test]
+    fn tag_groups() {
+        let file = "#+TAGS: { @home(h) @office(o) } laptop(l)\n#+TAGS: [ Work : meeting report ] [ Job : Work admin ] { Context : @phone @mail }\n";
+        let groups = TagGroups::from_file(file);
+        assert_eq!(groups.groups.len(), 4);
+        assert!(groups.groups[0].exclusive);
+        assert_eq!(groups.groups[0].name, None);
+        assert_eq!(groups.groups[0].members, ["@home", "@office"]);
+        assert_eq!(groups.groups[1].name.as_deref(), Some("Work"));
+        assert_eq!(groups.group_tags("meeting"), ["Work", "Job"]);
+        assert_eq!(groups.group_tags("@mail"), ["Context"]);
+        assert!(groups.group_tags("@home").is_empty());
+        assert!(groups.group_tags("laptop").is_empty());
+    }
+}
@@ -0,0 +1,639 @@
+//! Grammar of org timestamps.
+//! Examples:
+//! - active: "<2023-09-05 Tue 10:00>", inactive: "[2023-09-05 Tue]"
+//! - time range: "<2023-09-05 Tue 10:00-11:30>"
+//! - date range: "<2023-09-05 Tue>--<2023-09-07 Thu>"
+//! - repeater and warning delay: "<2023-09-05 Tue +1w -3d>", "<2023-09-05 Tue .+2m --2d>"
+use chrono::{Datelike, Days, Duration, Months, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use std::error::Error;
+use std::fmt;
+
+/// A point in time. The time is optional, as org allows dates without time.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Moment {
+    pub date: NaiveDate,
+    pub time: Option<NaiveTime>,
+}
+
+/// Unit of a repeater or a warning delay.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum TimeUnit {
+    Hour,
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+/// Kind of repeater, i.e. how the timestamp is shifted once the task is done.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum RepeaterKind {
+    /// "+": shift by the interval once
+    Cumulate,
+    /// "++": shift by the interval until the date is in the future
+    CatchUp,
+    /// ".+": shift by the interval from the completion date
+    Restart,
+}
+
+/// Repeater of a timestamp. Example: "+1w"
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Repeater {
+    pub kind: RepeaterKind,
+    pub value: u32,
+    pub unit: TimeUnit,
+}
+
+/// Kind of warning delay.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum DelayKind {
+    /// "-": applies to every occurrence of a repeating timestamp
+    All,
+    /// "--": applies to the first occurrence only
+    First,
+}
+
+/// Warning delay of a timestamp. Example: "-3d"
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Delay {
+    pub kind: DelayKind,
+    pub value: u32,
+    pub unit: TimeUnit,
+}
+
+/// An org timestamp.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Timestamp {
+    /// Active timestamps ("<...>") show up in the agenda, inactive ones ("[...]") do not
+    pub active: bool,
+    pub start: Moment,
+    /// End of a time range or of a date range
+    pub end: Option<Moment>,
+    pub repeater: Option<Repeater>,
+    pub delay: Option<Delay>,
+}
+
+/// Error returned when a timestamp cannot be parsed.
+/// The column is the byte offset of the failure inside the parsed text.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TimestampError {
+    pub column: usize,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for TimestampError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invalid timestamp at column {}: {}",
+            self.column, self.reason
+        )
+    }
+}
+
+impl Error for TimestampError {}
+
+impl Moment {
+    /// Date and time of the moment. A moment without time starts at midnight.
+    pub fn datetime(&self) -> NaiveDateTime {
+        self.date.and_time(self.time.unwrap_or_default())
+    }
+}
+
+impl TimeUnit {
+    fn parse(c: char) -> Option<TimeUnit> {
+        match c {
+            'h' => Some(TimeUnit::Hour),
+            'd' => Some(TimeUnit::Day),
+            'w' => Some(TimeUnit::Week),
+            'm' => Some(TimeUnit::Month),
+            'y' => Some(TimeUnit::Year),
+            _ => None,
+        }
+    }
+    fn as_char(&self) -> char {
+        match self {
+            TimeUnit::Hour => 'h',
+            TimeUnit::Day => 'd',
+            TimeUnit::Week => 'w',
+            TimeUnit::Month => 'm',
+            TimeUnit::Year => 'y',
+        }
+    }
+}
+
+impl Timestamp {
+    /// Inactive timestamp of a date and time, to the minute. Example: "[2023-09-05 Tue 10:00]"
+    pub fn inactive(at: NaiveDateTime) -> Timestamp {
+        Timestamp {
+            active: false,
+            start: Moment {
+                date: at.date(),
+                time: NaiveTime::from_hms_opt(at.hour(), at.minute(), 0),
+            },
+            end: None,
+            repeater: None,
+            delay: None,
+        }
+    }
+    /// Parse a timestamp at the start of `text`.
+    /// Returns the timestamp and the text following it.
+    pub fn parse(text: &str) -> Result<(Timestamp, &str), TimestampError> {
+        let (mut timestamp, rest) = Self::parse_single(text, 0)?;
+        // Date range: "<a>--<b>"
+        if timestamp.end.is_none() {
+            if let Some(second) = rest.strip_prefix("--") {
+                let column = text.len() - second.len();
+                if let Ok((end, rest)) = Self::parse_single(second, column) {
+                    if end.active == timestamp.active {
+                        timestamp.end = Some(end.start);
+                        return Ok((timestamp, rest));
+                    }
+                }
+            }
+        }
+        Ok((timestamp, rest))
+    }
+    /// Find the first valid timestamp inside a line, active or inactive.
+    pub fn find(line: &str) -> Option<Timestamp> {
+        Self::find_all(line).into_iter().next()
+    }
+    /// Find the first valid active timestamp inside a line.
+    pub fn find_active(line: &str) -> Option<Timestamp> {
+        Self::find_all(line).into_iter().find(|t| t.active)
+    }
+    /// Find all the valid timestamps inside a line.
+    /// Brackets that do not start a valid timestamp are skipped.
+    pub fn find_all(line: &str) -> Vec<Timestamp> {
+        let mut timestamps = vec![];
+        let mut rest = line;
+        while let Some(i) = rest.find(['<', '[']) {
+            match Self::parse(&rest[i..]) {
+                Ok((timestamp, after)) => {
+                    timestamps.push(timestamp);
+                    rest = after;
+                }
+                Err(_) => rest = &rest[i + 1..],
+            }
+        }
+        timestamps
+    }
+    /// Date and time at which the timestamp starts.
+    pub fn datetime(&self) -> NaiveDateTime {
+        self.start.datetime()
+    }
+    /// Parse a single bracketed timestamp, without date range.
+    /// `offset` is the column of `text` in the original input, used for errors.
+    fn parse_single(text: &str, offset: usize) -> Result<(Timestamp, &str), TimestampError> {
+        let error = |column: usize, reason| TimestampError {
+            column: offset + column,
+            reason,
+        };
+        let (active, close) = match text.chars().next() {
+            Some('<') => (true, '>'),
+            Some('[') => (false, ']'),
+            _ => return Err(error(0, "expected '<' or '['")),
+        };
+        let end = text
+            .find(close)
+            .ok_or(error(0, "missing closing bracket"))?;
+        let content = &text[1..end];
+        let column_of = |token: &str| 1 + token.as_ptr() as usize - content.as_ptr() as usize;
+
+        let mut tokens = content.split_whitespace();
+        let date_token = tokens.next().ok_or(error(1, "empty timestamp"))?;
+        let date = NaiveDate::parse_from_str(date_token, "%Y-%m-%d")
+            .map_err(|_| error(column_of(date_token), "expected a date as YYYY-MM-DD"))?;
+        let mut timestamp = Timestamp {
+            active,
+            start: Moment { date, time: None },
+            end: None,
+            repeater: None,
+            delay: None,
+        };
+        for token in tokens {
+            let column = column_of(token);
+            if token.starts_with(|c: char| c.is_ascii_digit()) {
+                if timestamp.start.time.is_some() {
+                    return Err(error(column, "duplicate time"));
+                }
+                let (start, end) = match token.split_once('-') {
+                    Some((start, end)) => (start, Some(end)),
+                    None => (token, None),
+                };
+                timestamp.start.time =
+                    Some(parse_time(start).ok_or(error(column, "expected a time as HH:MM"))?);
+                if let Some(end) = end {
+                    let time = parse_time(end).ok_or(error(column, "expected a time as HH:MM"))?;
+                    timestamp.end = Some(Moment {
+                        date,
+                        time: Some(time),
+                    });
+                }
+            } else if let Some(repeater) = parse_repeater(token) {
+                if timestamp.repeater.replace(repeater).is_some() {
+                    return Err(error(column, "duplicate repeater"));
+                }
+            } else if let Some(delay) = parse_delay(token) {
+                if timestamp.delay.replace(delay).is_some() {
+                    return Err(error(column, "duplicate warning delay"));
+                }
+            } else if token.chars().all(|c| c.is_alphabetic() || c == '.') {
+                // Day name. It is ignored, like org does
+            } else {
+                return Err(error(column, "unexpected token"));
+            }
+        }
+        Ok((timestamp, &text[end + 1..]))
+    }
+}
+
+/// Parse a time as "HH:MM" or "H:MM"
+fn parse_time(text: &str) -> Option<NaiveTime> {
+    let (hour, minute) = text.split_once(':')?;
+    if hour.is_empty() || hour.len() > 2 || minute.len() != 2 {
+        return None;
+    }
+    NaiveTime::from_hms_opt(hour.parse().ok()?, minute.parse().ok()?, 0)
+}
+
+/// Parse an interval as "1w"
+fn parse_interval(text: &str) -> Option<(u32, TimeUnit)> {
+    let unit = TimeUnit::parse(text.chars().last()?)?;
+    let value = text[..text.len() - 1].parse().ok()?;
+    Some((value, unit))
+}
+
+/// Parse a repeater as "+1w", "++1w" or ".+1w"
+fn parse_repeater(token: &str) -> Option<Repeater> {
+    let (kind, interval) = if let Some(rest) = token.strip_prefix("++") {
+        (RepeaterKind::CatchUp, rest)
+    } else if let Some(rest) = token.strip_prefix(".+") {
+        (RepeaterKind::Restart, rest)
+    } else {
+        (RepeaterKind::Cumulate, token.strip_prefix('+')?)
+    };
+    let (value, unit) = parse_interval(interval)?;
+    Some(Repeater { kind, value, unit })
+}
+
+/// Parse a warning delay as "-3d" or "--3d"
+fn parse_delay(token: &str) -> Option<Delay> {
+    let (kind, interval) = match token.strip_prefix("--") {
+        Some(rest) => (DelayKind::First, rest),
+        None => (DelayKind::All, token.strip_prefix('-')?),
+    };
+    let (value, unit) = parse_interval(interval)?;
+    Some(Delay { kind, value, unit })
+}
+
+impl Repeater {
+    /// Add `count` times the interval of the repeater to a date and time.
+    /// Months and years are added on the calendar, and the day is clamped to the end of
+    /// the month. Example: January 31 + 1m is February 28 (or 29 on a leap year).
+    /// Returns None on overflow.
+    pub fn add_to(&self, datetime: NaiveDateTime, count: u32) -> Option<NaiveDateTime> {
+        let value = self.value.checked_mul(count)?;
+        match self.unit {
+            TimeUnit::Hour => datetime.checked_add_signed(Duration::try_hours(value.into())?),
+            TimeUnit::Day => datetime.checked_add_days(Days::new(value.into())),
+            TimeUnit::Week => datetime.checked_add_days(Days::new(u64::from(value) * 7)),
+            TimeUnit::Month => datetime.checked_add_months(Months::new(value)),
+            TimeUnit::Year => datetime.checked_add_months(Months::new(value.checked_mul(12)?)),
+        }
+    }
+    /// Lower bound of the number of intervals between `from` and `to`.
+    fn count_between(&self, from: NaiveDateTime, to: NaiveDateTime) -> u32 {
+        if to <= from || self.value == 0 {
+            return 0;
+        }
+        let intervals = match self.unit {
+            TimeUnit::Hour => (to - from).num_hours(),
+            TimeUnit::Day => (to - from).num_days(),
+            TimeUnit::Week => (to - from).num_weeks(),
+            TimeUnit::Month | TimeUnit::Year => {
+                let months = |d: NaiveDateTime| i64::from(d.year()) * 12 + i64::from(d.month0());
+                let months = months(to) - months(from) - 1;
+                if self.unit == TimeUnit::Year {
+                    months / 12
+                } else {
+                    months
+                }
+            }
+        };
+        u32::try_from(intervals.max(0) / i64::from(self.value)).unwrap_or(u32::MAX)
+    }
+}
+
+impl Timestamp {
+    /// The next `n` occurrences of the timestamp strictly after `after`.
+    /// Without repeater, the only occurrence is the start of the timestamp.
+    /// Occurrences of every kind of repeater are on the grid start + k * interval: "+" and
+    /// "++" keep that grid, and ".+" restarts from the day the task is done, which is the
+    /// day of the occurrence when the task is done on time.
+    pub fn next_occurrences(&self, after: NaiveDateTime, n: usize) -> Vec<NaiveDateTime> {
+        let start = self.datetime();
+        let Some(repeater) = self.repeater.filter(|r| r.value > 0) else {
+            return Some(start)
+                .filter(|s| *s > after)
+                .into_iter()
+                .take(n)
+                .collect();
+        };
+        let first = repeater.count_between(start, after);
+        (first..)
+            .map_while(|count| repeater.add_to(start, count))
+            .skip_while(|occurrence| *occurrence <= after)
+            .take(n)
+            .collect()
+    }
+    /// The first occurrence of the timestamp strictly after `after`.
+    pub fn next_occurrence(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        self.next_occurrences(after, 1).into_iter().next()
+    }
+    /// The timestamp shifted by its repeater, once the task is done at `done_at`, like org does:
+    /// - "+1w" shifts the timestamp by one week, even if it stays in the past;
+    /// - "++1w" shifts it by as many weeks as needed to be in the future, keeping the weekday;
+    /// - ".+1w" shifts it to one week after the day it was done.
+    ///
+    /// Returns None if there is no repeater.
+    pub fn repeat(&self, done_at: NaiveDateTime) -> Option<Timestamp> {
+        let repeater = self.repeater.filter(|r| r.value > 0)?;
+        let start = self.datetime();
+        let shifted = match repeater.kind {
+            RepeaterKind::Cumulate => repeater.add_to(start, 1)?,
+            RepeaterKind::CatchUp => {
+                let in_future = |d: &NaiveDateTime| match (self.start.time, repeater.unit) {
+                    (Some(_), _) | (None, TimeUnit::Hour) => *d > done_at,
+                    (None, _) => d.date() > done_at.date(),
+                };
+                let first = repeater.count_between(start, done_at).max(1);
+                (first..)
+                    .map_while(|count| repeater.add_to(start, count))
+                    .find(in_future)?
+            }
+            RepeaterKind::Restart if repeater.unit == TimeUnit::Hour => {
+                repeater.add_to(done_at, 1)?
+            }
+            RepeaterKind::Restart => repeater.add_to(done_at.date().and_time(start.time()), 1)?,
+        };
+        let moved = |moment: &Moment| {
+            let datetime = moment.datetime() + (shifted - start);
+            Moment {
+                date: datetime.date(),
+                time: moment.time.map(|_| datetime.time()),
+            }
+        };
+        Some(Timestamp {
+            start: moved(&self.start),
+            end: self.end.as_ref().map(moved),
+            ..self.clone()
+        })
+    }
+}
+
+impl fmt::Display for Repeater {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            RepeaterKind::Cumulate => "+",
+            RepeaterKind::CatchUp => "++",
+            RepeaterKind::Restart => ".+",
+        };
+        write!(f, "{kind}{}{}", self.value, self.unit.as_char())
+    }
+}
+
+impl fmt::Display for Delay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            DelayKind::All => "-",
+            DelayKind::First => "--",
+        };
+        write!(f, "{kind}{}{}", self.value, self.unit.as_char())
+    }
+}
+
+impl fmt::Display for Timestamp {
+    /// Write the timestamp the way org does. Example: "<2023-09-05 Tue 10:00-11:30 +1w -3d>"
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (open, close) = if self.active { ('<', '>') } else { ('[', ']') };
+        let write_moment =
+            |f: &mut fmt::Formatter, moment: &Moment, end_time: Option<NaiveTime>| {
+                write!(f, "{open}{}", moment.date.format("%Y-%m-%d %a"))?;
+                if let Some(time) = moment.time {
+                    write!(f, " {}", time.format("%H:%M"))?;
+                }
+                if let Some(time) = end_time {
+                    write!(f, "-{}", time.format("%H:%M"))?;
+                }
+                if let Some(repeater) = &self.repeater {
+                    write!(f, " {repeater}")?;
+                }
+                if let Some(delay) = &self.delay {
+                    write!(f, " {delay}")?;
+                }
+                write!(f, "{close}")
+            };
+        match self.end {
+            Some(end) if end.date == self.start.date && self.start.time.is_some() => {
+                write_moment(f, &self.start, end.time)
+            }
+            Some(end) => {
+                write_moment(f, &self.start, None)?;
+                write!(f, "--")?;
+                write_moment(f, &end, None)
+            }
+            None => write_moment(f, &self.start, None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+    fn time(h: u32, m: u32) -> Option<NaiveTime> {
+        NaiveTime::from_hms_opt(h, m, 0)
+    }
+
+    #[test]
+    fn simple_timestamps() {
+        let (ts, rest) = Timestamp::parse("<2023-09-05 Tue 10:00> rest").unwrap();
+        assert!(ts.active);
+        assert_eq!(ts.start.date, date(2023, 9, 5));
+        assert_eq!(ts.start.time, time(10, 0));
+        assert_eq!(rest, " rest");
+
+        let (ts, _) = Timestamp::parse("[2023-09-05 Tue]").unwrap();
+        assert!(!ts.active);
+        assert_eq!(ts.start.time, None);
+
+        // Spaces inside the brackets and a wrong day name are accepted
+        let (ts, _) = Timestamp::parse("< 2023-05-18 Sun 9:05 >").unwrap();
+        assert_eq!(ts.start.date, date(2023, 5, 18));
+        assert_eq!(ts.start.time, time(9, 5));
+    }
+
+    #[test]
+    fn ranges() {
+        let (ts, _) = Timestamp::parse("<2023-09-05 Tue 10:00-11:30>").unwrap();
+        assert_eq!(
+            ts.end,
+            Some(Moment {
+                date: date(2023, 9, 5),
+                time: time(11, 30)
+            })
+        );
+        let (ts, rest) = Timestamp::parse("<2023-09-05 Tue>--<2023-09-07 Thu 12:00>.").unwrap();
+        assert_eq!(ts.end.unwrap().date, date(2023, 9, 7));
+        assert_eq!(ts.end.unwrap().time, time(12, 0));
+        assert_eq!(rest, ".");
+    }
+
+    #[test]
+    fn repeaters_and_delays() {
+        let (ts, _) = Timestamp::parse("<2023-09-05 Tue +1w -3d>").unwrap();
+        let repeater = ts.repeater.unwrap();
+        assert_eq!(repeater.kind, RepeaterKind::Cumulate);
+        assert_eq!((repeater.value, repeater.unit), (1, TimeUnit::Week));
+        let delay = ts.delay.unwrap();
+        assert_eq!(delay.kind, DelayKind::All);
+        assert_eq!((delay.value, delay.unit), (3, TimeUnit::Day));
+
+        let (ts, _) = Timestamp::parse("<2023-09-05 Tue 08:00 ++1d --2d>").unwrap();
+        assert_eq!(ts.repeater.unwrap().kind, RepeaterKind::CatchUp);
+        assert_eq!(ts.delay.unwrap().kind, DelayKind::First);
+        let (ts, _) = Timestamp::parse("<2023-09-05 Tue .+2m>").unwrap();
+        assert_eq!(ts.repeater.unwrap().kind, RepeaterKind::Restart);
+        assert_eq!(ts.repeater.unwrap().unit, TimeUnit::Month);
+    }
+
+    #[test]
+    fn invalid_timestamps() {
+        assert!(Timestamp::parse("<Sun 10:10>").is_err());
+        assert!(Timestamp::parse("<10:10>").is_err());
+        assert!(Timestamp::parse("<2023-09-05 Tue").is_err());
+        assert!(Timestamp::parse("<2023-02-30 Thu>").is_err());
+        let error = Timestamp::parse("<2023-09-05 Tue 25:00>").unwrap_err();
+        assert_eq!(error.column, 16);
+    }
+
+    #[test]
+    fn find_in_line() {
+        let line = "* TODO compare a <b and [x] <2023-08-08 Tue 10:10> [2023-08-01 Tue]";
+        let ts = Timestamp::find(line).unwrap();
+        assert_eq!(ts.start.date, date(2023, 8, 8));
+        assert_eq!(Timestamp::find_all(line).len(), 2);
+        assert!(Timestamp::find("no timestamp <here>").is_none());
+    }
+
+    #[test]
+    fn display() {
+        for text in [
+            "<2023-09-05 Tue 10:00-11:30 +1w -3d>",
+            "[2023-09-05 Tue]",
+            "<2023-09-05 Tue>--<2023-09-07 Thu>",
+            "<2023-09-05 Tue 08:00 .+2m --2d>",
+        ] {
+            let (ts, _) = Timestamp::parse(text).unwrap();
+            assert_eq!(ts.to_string(), text);
+        }
+    }
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> NaiveDateTime {
+        date(y, m, d).and_hms_opt(h, min, 0).unwrap()
+    }
+
+    #[test]
+    fn weekly_occurrences() {
+        let (ts, _) = Timestamp::parse("<2023-01-02 Mon 09:00 +1w>").unwrap();
+        let next = ts.next_occurrences(at(2023, 9, 5, 12, 0), 3);
+        assert_eq!(
+            next,
+            [
+                at(2023, 9, 11, 9, 0),
+                at(2023, 9, 18, 9, 0),
+                at(2023, 9, 25, 9, 0)
+            ]
+        );
+        // Strictly after the given instant
+        assert_eq!(
+            ts.next_occurrence(at(2023, 9, 11, 9, 0)),
+            Some(at(2023, 9, 18, 9, 0))
+        );
+        // Before the start, the first occurrence is the start itself
+        assert_eq!(
+            ts.next_occurrence(at(2022, 1, 1, 0, 0)),
+            Some(at(2023, 1, 2, 9, 0))
+        );
+
+        let (ts, _) = Timestamp::parse("<2023-01-02 Mon 09:00>").unwrap();
+        assert_eq!(ts.next_occurrences(at(2023, 1, 1, 0, 0), 3).len(), 1);
+        assert!(ts.next_occurrence(at(2023, 9, 5, 0, 0)).is_none());
+    }
+
+    #[test]
+    fn month_end_and_leap_year() {
+        let (ts, _) = Timestamp::parse("<2023-01-31 Tue +1m>").unwrap();
+        let next = ts.next_occurrences(at(2023, 1, 31, 0, 0), 3);
+        assert_eq!(
+            next,
+            [
+                at(2023, 2, 28, 0, 0),
+                at(2023, 3, 31, 0, 0),
+                at(2023, 4, 30, 0, 0)
+            ]
+        );
+        let (ts, _) = Timestamp::parse("<2024-02-29 Thu +1y>").unwrap();
+        let next = ts.next_occurrences(at(2024, 3, 1, 0, 0), 4);
+        assert_eq!(
+            next,
+            [
+                at(2025, 2, 28, 0, 0),
+                at(2026, 2, 28, 0, 0),
+                at(2027, 2, 28, 0, 0),
+                at(2028, 2, 29, 0, 0)
+            ]
+        );
+        let (ts, _) = Timestamp::parse("<2023-09-05 Tue 10:00 +6h>").unwrap();
+        assert_eq!(
+            ts.next_occurrence(at(2023, 9, 6, 11, 0)),
+            Some(at(2023, 9, 6, 16, 0))
+        );
+    }
+
+    #[test]
+    fn repeat_when_done() {
+        let done_at = at(2023, 9, 20, 18, 0);
+        let repeat = |text| {
+            let (ts, _) = Timestamp::parse(text).unwrap();
+            ts.repeat(done_at).map(|t| t.to_string())
+        };
+        assert_eq!(
+            repeat("<2023-09-04 Mon +1w>").as_deref(),
+            Some("<2023-09-11 Mon +1w>")
+        );
+        assert_eq!(
+            repeat("<2023-09-04 Mon ++1w>").as_deref(),
+            Some("<2023-09-25 Mon ++1w>")
+        );
+        assert_eq!(
+            repeat("<2023-09-04 Mon 08:00-09:00 .+1w>").as_deref(),
+            Some("<2023-09-27 Wed 08:00-09:00 .+1w>")
+        );
+        assert_eq!(repeat("<2023-09-04 Mon>"), None);
+    }
+}
@@ -0,0 +1,235 @@
+//! Reminders fired n minutes before each event.
+//! The daemon keeps a time-ordered queue of upcoming reminders, sleeps until the next one is
+//! due, fires it, and rebuilds the queue when the todos change.
+//! A task with an ":APPT_WARNTIME:" property is reminded that many minutes before its event
+//! only, instead of at the configured lead times.
+use crate::parsing::Todo;
+use chrono::{Duration, Local, NaiveDateTime};
+use std::collections::BTreeMap;
+use std::fmt;
+use tokio::sync::mpsc::Receiver;
+use tokio::time::sleep;
+
+/// Property overriding the lead times of a single task, in minutes.
+pub const WARNTIME_PROPERTY: &str = "APPT_WARNTIME";
+
+/// Longest time the daemon sleeps without looking at the wall clock again.
+/// The wall clock can jump (suspend, DST), while tokio sleeps on a monotonic clock.
+const MAX_SLEEP: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// A single reminder: `lead` before `event`, notify about `todo`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Reminder {
+    pub todo: Todo,
+    pub event: NaiveDateTime,
+    pub lead: Duration,
+}
+
+/// Time-ordered queue of upcoming reminders.
+/// Reminders with the same due time are fired in insertion order.
+#[derive(Debug, Default)]
+pub struct ReminderQueue {
+    reminders: BTreeMap<(NaiveDateTime, u64), Reminder>,
+    next_id: u64,
+}
+
+impl Reminder {
+    /// Date and time at which the reminder must be fired.
+    pub fn due(&self) -> NaiveDateTime {
+        self.event - self.lead
+    }
+}
+
+impl fmt::Display for Reminder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let title = self.todo.title();
+        let minutes = self.lead.num_minutes();
+        let time = self.event.format("%H:%M");
+        write!(f, "{title} in {minutes} min, at {time}")
+    }
+}
+
+impl ReminderQueue {
+    /// Build the queue of the reminders due after `now`.
+    /// Every todo with a time gets one reminder per lead time, for its next occurrence.
+    /// Todos with a date but no time are skipped, as there is no moment to be reminded before.
+    pub fn new(todos: &[Todo], lead_times: &[Duration], now: NaiveDateTime) -> ReminderQueue {
+        let mut queue = ReminderQueue::default();
+        for todo in todos.iter().filter(|t| t.is_timed()) {
+            match warntime(todo) {
+                Some(lead) => queue.schedule_after(todo, lead, now + lead),
+                None => {
+                    for lead in lead_times {
+                        queue.schedule_after(todo, *lead, now + *lead);
+                    }
+                }
+            }
+        }
+        queue
+    }
+    /// Schedule the reminder for the first occurrence of `todo` strictly after `event_after`.
+    fn schedule_after(&mut self, todo: &Todo, lead: Duration, event_after: NaiveDateTime) {
+        if let Some(event) = todo.next_occurrences(event_after, 1).pop() {
+            let reminder = Reminder {
+                todo: todo.clone(),
+                event,
+                lead,
+            };
+            self.reminders
+                .insert((reminder.due(), self.next_id), reminder);
+            self.next_id += 1;
+        }
+    }
+    /// Date and time of the next reminder to fire.
+    pub fn next_due(&self) -> Option<NaiveDateTime> {
+        self.reminders.keys().next().map(|(due, _)| *due)
+    }
+    /// Remove and return the reminders due at or before `now`.
+    /// Repeating todos are scheduled again for their next occurrence.
+    pub fn pop_due(&mut self, now: NaiveDateTime) -> Vec<Reminder> {
+        let mut due = vec![];
+        while let Some(entry) = self.reminders.first_entry() {
+            if entry.key().0 > now {
+                break;
+            }
+            let reminder = entry.remove();
+            self.schedule_after(&reminder.todo, reminder.lead, reminder.event);
+            due.push(reminder);
+        }
+        due
+    }
+    pub fn len(&self) -> usize {
+        self.reminders.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.reminders.is_empty()
+    }
+}
+
+/// Lead time set by the ":APPT_WARNTIME:" property of a todo, if it is a number of minutes.
+fn warntime(todo: &Todo) -> Option<Duration> {
+    let minutes: i64 = todo.property(WARNTIME_PROPERTY)?.parse().ok()?;
+    Duration::try_minutes(minutes).filter(|lead| *lead >= Duration::zero())
+}
+
+/// Current local date and time, as found in org files.
+pub fn now() -> NaiveDateTime {
+    Local::now().naive_local()
+}
+
+/// Run the reminder daemon.
+/// `fire` is called for every reminder when it is due. Every todo set received on `updates`
+/// replaces the current one and the queue is rebuilt.
+/// Returns once `updates` is closed and there is no reminder left.
+pub async fn run<F>(
+    todos: Vec<Todo>,
+    lead_times: Vec<Duration>,
+    mut updates: Receiver<Vec<Todo>>,
+    mut fire: F,
+) where
+    F: FnMut(&Reminder),
+{
+    let mut queue = ReminderQueue::new(&todos, &lead_times, now());
+    let mut listening = true;
+    loop {
+        let wait = match queue.next_due() {
+            Some(due) => (due - now()).to_std().unwrap_or_default().min(MAX_SLEEP),
+            None if !listening => return,
+            None => MAX_SLEEP,
+        };
+        tokio::select! {
+            _ = sleep(wait) => {
+                for reminder in queue.pop_due(now()) {
+                    fire(&reminder);
+                }
+            }
+            update = updates.recv(), if listening => match update {
+                Some(todos) => queue = ReminderQueue::new(&todos, &lead_times, now()),
+                None => listening = false,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReminderQueue;
+    use crate::parsing::{parse_file, Todo};
+    use chrono::{Duration, NaiveDate, NaiveDateTime};
+    use std::path::Path;
+
+    fn at(d: u32, h: u32, m: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2023, 9, d)
+            .unwrap()
+            .and_hms_opt(h, m, 0)
+            .unwrap()
+    }
+    fn todo(heading: &str, planning: &str) -> Todo {
+        Todo::parse_entry(heading, Some(planning)).unwrap()
+    }
+
+    #[test]
+    fn ordered_reminders() {
+        let todos = vec![
+            todo("* TODO Late", "SCHEDULED: <2023-09-05 Tue 14:00>"),
+            todo("* TODO Early", "SCHEDULED: <2023-09-05 Tue 10:00>"),
+            todo("* TODO No time", "SCHEDULED: <2023-09-05 Tue>"),
+            todo("* TODO Past", "SCHEDULED: <2023-09-04 Mon 10:00>"),
+        ];
+        let leads = [Duration::minutes(30), Duration::minutes(5)];
+        let mut queue = ReminderQueue::new(&todos, &leads, at(5, 8, 0));
+        assert_eq!(queue.len(), 4);
+        assert_eq!(queue.next_due(), Some(at(5, 9, 30)));
+
+        let fired = queue.pop_due(at(5, 9, 55));
+        let fired: Vec<String> = fired.iter().map(|r| r.to_string()).collect();
+        assert_eq!(
+            fired,
+            ["Early in 30 min, at 10:00", "Early in 5 min, at 10:00"]
+        );
+        assert_eq!(queue.next_due(), Some(at(5, 13, 30)));
+    }
+
+    #[test]
+    fn missed_lead_time_is_skipped() {
+        let todos = vec![todo("* TODO Soon", "SCHEDULED: <2023-09-05 Tue 10:00>")];
+        let leads = [Duration::minutes(30), Duration::minutes(5)];
+        let queue = ReminderQueue::new(&todos, &leads, at(5, 9, 40));
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.next_due(), Some(at(5, 9, 55)));
+    }
+
+    #[test]
+    fn warntime_overrides_lead_times() {
+        let file = "* TODO Dentist\nSCHEDULED: <2023-09-05 Tue 10:00>\n:PROPERTIES:\n:APPT_WARNTIME: 60\n:END:\n* TODO Call\nSCHEDULED: <2023-09-05 Tue 11:00>\n";
+        let todos = parse_file(Path::new("tasks.org"), file, &Default::default()).value;
+        let leads = [Duration::minutes(30), Duration::minutes(5)];
+        let mut queue = ReminderQueue::new(&todos, &leads, at(5, 8, 0));
+        assert_eq!(queue.len(), 3);
+        let fired: Vec<String> = queue
+            .pop_due(at(5, 11, 0))
+            .iter()
+            .map(|r| r.to_string())
+            .collect();
+        assert_eq!(
+            fired,
+            [
+                "Dentist in 60 min, at 10:00",
+                "Call in 30 min, at 11:00",
+                "Call in 5 min, at 11:00"
+            ]
+        );
+    }
+
+    #[test]
+    fn repeating_todo_is_rescheduled() {
+        let todos = vec![todo(
+            "* TODO Standup",
+            "SCHEDULED: <2023-09-04 Mon 09:00 +1d>",
+        )];
+        let mut queue = ReminderQueue::new(&todos, &[Duration::minutes(5)], at(5, 8, 0));
+        assert_eq!(queue.next_due(), Some(at(5, 8, 55)));
+        assert_eq!(queue.pop_due(at(5, 8, 55)).len(), 1);
+        assert_eq!(queue.next_due(), Some(at(6, 8, 55)));
+    }
+}
@@ -0,0 +1,204 @@
+//! Clock report: the time clocked in the logbooks, summed by file, tag or heading over a date
+//! range. Clocks are cut at the bounds of the range, and a running clock runs until now.
+use crate::parsing::document::Document;
+use chrono::{Duration, NaiveDateTime};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// Name of the row of the clocks of headings without any tag.
+const UNTAGGED: &str = "(untagged)";
+
+/// What the clocked time is summed by.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ClockGroup {
+    #[default]
+    File,
+    /// A clock counts for every tag of its heading, inherited tags included
+    Tag,
+    /// A clock counts for its own heading only, not for the parents
+    Heading,
+}
+
+/// Error returned when a clock group is unknown.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UnknownGroup(pub String);
+
+/// The clocked time of each group, and the total clocked time.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ClockReport {
+    pub group: ClockGroup,
+    /// Clocked time by group name, without the groups with no time
+    pub rows: BTreeMap<String, Duration>,
+    pub total: Duration,
+}
+
+impl ClockReport {
+    /// Sum the time clocked in `documents` between `from` and `to`.
+    pub fn new<'a, I>(
+        documents: I,
+        group: ClockGroup,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+        now: NaiveDateTime,
+    ) -> ClockReport
+    where
+        I: IntoIterator<Item = &'a Document>,
+    {
+        let mut report = ClockReport {
+            group,
+            ..Default::default()
+        };
+        for doc in documents {
+            let file = doc.path.display().to_string();
+            doc.visit(|heading, inherited| {
+                let time = heading
+                    .logbook
+                    .clocks
+                    .iter()
+                    .map(|c| c.duration_between(from, to, now))
+                    .fold(Duration::zero(), |sum, d| sum + d);
+                if time.is_zero() {
+                    return;
+                }
+                report.total += time;
+                let names = match group {
+                    ClockGroup::File => vec![file.clone()],
+                    ClockGroup::Heading => {
+                        let mut outline = inherited.outline.clone();
+                        outline.push(heading.title.clone());
+                        vec![format!("{file}: {}", outline.join(" / "))]
+                    }
+                    ClockGroup::Tag => {
+                        let mut tags = heading.tags.clone();
+                        for tag in &inherited.tags {
+                            if !tags.contains(tag) {
+                                tags.push(tag.clone());
+                            }
+                        }
+                        if tags.is_empty() {
+                            tags.push(String::from(UNTAGGED));
+                        }
+                        tags
+                    }
+                };
+                for name in names {
+                    *report.rows.entry(name).or_insert_with(Duration::zero) += time;
+                }
+            });
+        }
+        report
+    }
+}
+
+/// Format a duration as org does in clock tables. Example: "12:05"
+pub fn format_duration(duration: Duration) -> String {
+    let minutes = duration.num_minutes();
+    format!("{}:{:02}", minutes / 60, minutes % 60)
+}
+
+impl fmt::Display for ClockReport {
+    /// Write the report as a table, with the total on the last line
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let header = match self.group {
+            ClockGroup::File => "File",
+            ClockGroup::Tag => "Tag",
+            ClockGroup::Heading => "Heading",
+        };
+        let width = self
+            .rows
+            .keys()
+            .map(|name| name.chars().count())
+            .chain([header.len(), "Total".len()])
+            .max()
+            .unwrap_or_default();
+        writeln!(f, "{header:width$}  Time")?;
+        for (name, time) in &self.rows {
+            writeln!(f, "{name:width$}  {}", format_duration(*time))?;
+        }
+        write!(f, "{:width$}  {}", "Total", format_duration(self.total))
+    }
+}
+
+impl FromStr for ClockGroup {
+    type Err = UnknownGroup;
+    /// Parse a group as "file", "tag" or "heading"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "file" => Ok(ClockGroup::File),
+            "tag" => Ok(ClockGroup::Tag),
+            "heading" => Ok(ClockGroup::Heading),
+            other => Err(UnknownGroup(String::from(other))),
+        }
+    }
+}
+
+impl fmt::Display for UnknownGroup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unknown clock group: {} (expected file, tag or heading)",
+            self.0
+        )
+    }
+}
+
+impl Error for UnknownGroup {}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClockGroup, ClockReport};
+    use crate::parsing::document::Document;
+    use chrono::{Duration, NaiveDate, NaiveDateTime};
+    use std::path::Path;
+
+    fn at(d: u32, h: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2023, 9, d)
+            .unwrap()
+            .and_hms_opt(h, 0, 0)
+            .unwrap()
+    }
+
+    fn documents() -> Vec<Document> {
+        let work = "#+FILETAGS: :work:\n* Website\n:LOGBOOK:\nCLOCK: [2023-09-05 Tue 10:00]--[2023-09-05 Tue 11:30] =>  1:30\n:END:\n** DONE Deploy :ops:\nCLOSED: [2023-09-06 Wed 12:00]\n:LOGBOOK:\nCLOCK: [2023-09-06 Wed 09:00]--[2023-09-06 Wed 12:00] =>  3:00\nCLOCK: [2023-08-30 Wed 09:00]--[2023-08-30 Wed 12:00] =>  3:00\n:END:\n";
+        let home = "* Garden\nCLOCK: [2023-09-07 Thu 17:00]\n";
+        [("work.org", work), ("home.org", home)]
+            .into_iter()
+            .map(|(path, source)| {
+                Document::parse(Path::new(path), source.into(), &Default::default()).value
+            })
+            .collect()
+    }
+
+    #[test]
+    fn clock_report() {
+        let docs = documents();
+        let report = |group| ClockReport::new(&docs, group, at(1, 0), at(8, 0), at(7, 18));
+        let by_file = report(ClockGroup::File);
+        assert_eq!(by_file.total, Duration::minutes(330));
+        assert_eq!(by_file.rows["work.org"], Duration::minutes(270));
+        assert_eq!(by_file.rows["home.org"], Duration::minutes(60));
+
+        let by_tag = report(ClockGroup::Tag);
+        assert_eq!(by_tag.rows["work"], Duration::minutes(270));
+        assert_eq!(by_tag.rows["ops"], Duration::minutes(180));
+        assert_eq!(by_tag.rows["(untagged)"], Duration::minutes(60));
+
+        let by_heading = report(ClockGroup::Heading);
+        assert_eq!(
+            by_heading.rows["work.org: Website / Deploy"],
+            Duration::minutes(180)
+        );
+        assert_eq!(
+            by_heading.to_string(),
+            "Heading                     Time\nhome.org: Garden            1:00\nwork.org: Website           1:30\nwork.org: Website / Deploy  3:00\nTotal                       5:30"
+        );
+    }
+
+    #[test]
+    fn groups() {
+        assert_eq!("tag".parse(), Ok(ClockGroup::Tag));
+        assert!("week".parse::<ClockGroup>().is_err());
+    }
+}
@@ -0,0 +1,328 @@
+//! Scheduling and deadlines from the command line, with dates read like org reads them.
+//! Examples of dates: "2023-10-01", "today", "tomorrow", "fri" (the next Friday, or today),
+//! "+2d" or "-1w" (from today), "++2d" (from the current date of the heading), each
+//! optionally followed by a time "14:00" or a time range "14:00-15:30". A time alone is for
+//! today. Without time, the times of the current timestamp are kept, as are its repeater and
+//! warning delay, so that "+1w" pushes a weekly meeting by a week.
+use crate::parsing::document::{Document, Heading};
+use crate::parsing::edit::{add_logbook_note, set_planning, Edit};
+use crate::parsing::timestamp::{Moment, Timestamp};
+use chrono::{Datelike, Days, Months, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// Which timestamp of the planning line is set.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PlanningKind {
+    Scheduled,
+    Deadline,
+}
+
+/// A date read from the command line, resolved against today and the current timestamp.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DateInput {
+    pub date: DateSpec,
+    /// Start and optional end time
+    pub time: Option<(NaiveTime, Option<NaiveTime>)>,
+}
+
+/// The date part of a date input.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DateSpec {
+    Today,
+    Date(NaiveDate),
+    /// The next day with this weekday, today included
+    Weekday(Weekday),
+    /// Days, weeks, months or years from today, or from the current date of the heading
+    Relative {
+        from_current: bool,
+        value: i64,
+        unit: char,
+    },
+}
+
+/// Error returned when a date input cannot be read.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InvalidDate(pub String);
+
+/// Edits of a reschedule, and the new timestamp.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Rescheduled {
+    pub edits: Vec<Edit>,
+    pub timestamp: Timestamp,
+}
+
+impl PlanningKind {
+    /// Keyword of the planning line. Example: "SCHEDULED:"
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            PlanningKind::Scheduled => "SCHEDULED:",
+            PlanningKind::Deadline => "DEADLINE:",
+        }
+    }
+    fn current<'a>(&self, heading: &'a Heading) -> Option<&'a Timestamp> {
+        match self {
+            PlanningKind::Scheduled => heading.planning.scheduled.as_ref(),
+            PlanningKind::Deadline => heading.planning.deadline.as_ref(),
+        }
+    }
+    /// Logbook note of a changed timestamp, as org-log-reschedule and org-log-redeadline write
+    /// it. Example: "- Rescheduled from \"[2023-09-05 Tue]\" on [2023-09-06 Wed 18:30]"
+    fn note(&self, old: &Timestamp, now: NaiveDateTime) -> String {
+        let what = match self {
+            PlanningKind::Scheduled => "Rescheduled",
+            PlanningKind::Deadline => "New deadline",
+        };
+        let old = Timestamp {
+            active: false,
+            ..old.clone()
+        };
+        format!("- {what} from \"{old}\" on {}", Timestamp::inactive(now))
+    }
+}
+
+impl DateInput {
+    /// The timestamp of the input. `current` is the timestamp being replaced, if any.
+    /// Returns None when the date is out of range.
+    pub fn resolve(&self, today: NaiveDate, current: Option<&Timestamp>) -> Option<Timestamp> {
+        let date = match self.date {
+            DateSpec::Today => today,
+            DateSpec::Date(date) => date,
+            DateSpec::Weekday(weekday) => {
+                let days = weekday.days_since(today.weekday());
+                today.checked_add_days(Days::new(days.into()))?
+            }
+            DateSpec::Relative {
+                from_current,
+                value,
+                unit,
+            } => {
+                let base = match (from_current, current) {
+                    (true, Some(current)) => current.start.date,
+                    _ => today,
+                };
+                shift(base, value, unit)?
+            }
+        };
+        let moment = |date, time| Moment { date, time };
+        let (start, end) = match (self.time, current) {
+            (Some((start, end)), _) => (
+                moment(date, Some(start)),
+                end.map(|e| moment(date, Some(e))),
+            ),
+            (None, Some(current)) => {
+                let delta = date - current.start.date;
+                let end = match current.end {
+                    Some(end) => Some(moment(end.date.checked_add_signed(delta)?, end.time)),
+                    None => None,
+                };
+                (moment(date, current.start.time), end)
+            }
+            (None, None) => (moment(date, None), None),
+        };
+        Some(Timestamp {
+            active: true,
+            start,
+            end,
+            repeater: current.and_then(|c| c.repeater),
+            delay: current.and_then(|c| c.delay),
+        })
+    }
+}
+
+/// Shift a date by `value` units of "d", "w", "m" or "y".
+fn shift(date: NaiveDate, value: i64, unit: char) -> Option<NaiveDate> {
+    let n = value.unsigned_abs();
+    match (unit, value < 0) {
+        ('d', false) => date.checked_add_days(Days::new(n)),
+        ('d', true) => date.checked_sub_days(Days::new(n)),
+        ('w', _) => shift(date, value.checked_mul(7)?, 'd'),
+        ('m', false) => date.checked_add_months(Months::new(n.try_into().ok()?)),
+        ('m', true) => date.checked_sub_months(Months::new(n.try_into().ok()?)),
+        ('y', _) => shift(date, value.checked_mul(12)?, 'm'),
+        _ => None,
+    }
+}
+
+/// Set the scheduled date or the deadline of `heading`. Only the bytes of the timestamp change
+/// when it exists. `log` adds a note with the replaced timestamp to the logbook.
+pub fn reschedule(
+    doc: &Document,
+    heading: &Heading,
+    kind: PlanningKind,
+    input: &DateInput,
+    now: NaiveDateTime,
+    log: bool,
+) -> Result<Rescheduled, InvalidDate> {
+    let current = kind.current(heading);
+    let timestamp = input
+        .resolve(now.date(), current)
+        .ok_or_else(|| InvalidDate(String::from("out of range")))?;
+    let mut edits = vec![set_planning(doc, heading, kind.keyword(), &timestamp)];
+    if let Some(old) = current.filter(|old| log && **old != timestamp) {
+        edits.push(add_logbook_note(doc, heading, &kind.note(old, now)));
+    }
+    Ok(Rescheduled { edits, timestamp })
+}
+
+impl FromStr for DateInput {
+    type Err = InvalidDate;
+    /// Parse a date and an optional time, or a time alone. Example: "+2d 14:00"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidDate(format!("{s} (expected e.g. 2023-10-01, +2d, fri or 14:00)"));
+        let mut date = None;
+        let mut time = None;
+        for word in s.split_whitespace() {
+            if word.contains(':') && time.is_none() {
+                time = Some(parse_time_range(word).ok_or_else(invalid)?);
+            } else if date.is_none() {
+                date = Some(parse_date(word).ok_or_else(invalid)?);
+            } else {
+                return Err(invalid());
+            }
+        }
+        if date.is_none() && time.is_none() {
+            return Err(invalid());
+        }
+        Ok(DateInput {
+            date: date.unwrap_or(DateSpec::Today),
+            time,
+        })
+    }
+}
+
+fn parse_date(word: &str) -> Option<DateSpec> {
+    let lower = word.to_ascii_lowercase();
+    match lower.as_str() {
+        "today" | "." => return Some(DateSpec::Today),
+        "tomorrow" => return Some(relative(false, 1, 'd')),
+        "yesterday" => return Some(relative(false, -1, 'd')),
+        _ => {}
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(word, "%Y-%m-%d") {
+        return Some(DateSpec::Date(date));
+    }
+    if let Ok(weekday) = lower.parse::<Weekday>() {
+        return Some(DateSpec::Weekday(weekday));
+    }
+    let (from_current, sign, rest) = match (lower.strip_prefix("++"), lower.strip_prefix("--")) {
+        (Some(rest), _) => (true, 1, rest),
+        (_, Some(rest)) => (true, -1, rest),
+        _ => match (lower.strip_prefix('+'), lower.strip_prefix('-')) {
+            (Some(rest), _) => (false, 1, rest),
+            (_, Some(rest)) => (false, -1, rest),
+            _ => return None,
+        },
+    };
+    let (number, unit) = match rest.char_indices().last()? {
+        (i, unit) if unit.is_ascii_alphabetic() => (&rest[..i], unit),
+        _ => (rest, 'd'),
+    };
+    let value: i64 = if number.is_empty() {
+        1
+    } else {
+        number.parse().ok()?
+    };
+    "dwmy"
+        .contains(unit)
+        .then(|| relative(from_current, sign * value, unit))
+}
+
+fn relative(from_current: bool, value: i64, unit: char) -> DateSpec {
+    DateSpec::Relative {
+        from_current,
+        value,
+        unit,
+    }
+}
+
+/// Parse "14:00" or "14:00-15:30".
+fn parse_time_range(word: &str) -> Option<(NaiveTime, Option<NaiveTime>)> {
+    let time = |t: &str| NaiveTime::parse_from_str(t, "%H:%M").ok();
+    match word.split_once('-') {
+        Some((start, end)) => Some((time(start)?, Some(time(end)?))),
+        None => Some((time(word)?, None)),
+    }
+}
+
+impl fmt::Display for InvalidDate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid date: {}", self.0)
+    }
+}
+
+impl Error for InvalidDate {}
+
+#[cfg(test)]
+mod tests {
+    use super::{reschedule, DateInput, PlanningKind};
+    use crate::parsing::document::Document;
+    use crate::parsing::timestamp::{Moment, Timestamp};
+    use chrono::NaiveDate;
+    use std::path::Path;
+
+    #[test]
+    fn date_inputs() {
+        let today = NaiveDate::from_ymd_opt(2023, 9, 6).unwrap();
+        let (current, _) = Timestamp::parse("<2023-09-05 Tue 10:00-11:00 +1w>").unwrap();
+        let resolve = |text: &str, current| {
+            let input: DateInput = text.parse().unwrap();
+            input.resolve(today, current).unwrap().to_string()
+        };
+        assert_eq!(resolve("+2d 14:00", None), "<2023-09-08 Fri 14:00>");
+        assert_eq!(resolve("2023-10-01", None), "<2023-10-01 Sun>");
+        assert_eq!(resolve("fri", None), "<2023-09-08 Fri>");
+        assert_eq!(resolve("wed", None), "<2023-09-06 Wed>");
+        assert_eq!(resolve("-1m", None), "<2023-08-06 Sun>");
+        assert_eq!(resolve("9:30-10:15", None), "<2023-09-06 Wed 09:30-10:15>");
+        assert_eq!(
+            resolve("++1w", Some(&current)),
+            "<2023-09-12 Tue 10:00-11:00 +1w>"
+        );
+        assert_eq!(
+            resolve("tomorrow 08:00", Some(&current)),
+            "<2023-09-07 Thu 08:00 +1w>"
+        );
+        let mut range = current.clone();
+        range.end = Some(Moment {
+            date: NaiveDate::MAX,
+            time: None,
+        });
+        let input: DateInput = "2023-10-01".parse().unwrap();
+        assert_eq!(input.resolve(today, Some(&range)), None);
+        for invalid in ["", "+2x", "2023-02-30", "14:00 15:00 16:00", "soon"] {
+            assert!(invalid.parse::<DateInput>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn reschedule_heading() {
+        let source = "* TODO Deploy\n  DEADLINE: <2023-09-08 Fri>  SCHEDULED: <2023-09-05 Tue +1w>\n:LOGBOOK:\n:END:\n";
+        let doc = Document::parse(Path::new("a.org"), source.into(), &Default::default()).value;
+        let now = NaiveDate::from_ymd_opt(2023, 9, 6)
+            .unwrap()
+            .and_hms_opt(18, 30, 0)
+            .unwrap();
+        let input = "+2d".parse().unwrap();
+        let edits = [PlanningKind::Scheduled, PlanningKind::Deadline]
+            .into_iter()
+            .flat_map(|kind| {
+                reschedule(
+                    &doc,
+                    &doc.headings[0],
+                    kind,
+                    &input,
+                    now,
+                    kind == PlanningKind::Scheduled,
+                )
+                .unwrap()
+                .edits
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            doc.apply(&edits),
+            "* TODO Deploy\n  DEADLINE: <2023-09-08 Fri>  SCHEDULED: <2023-09-08 Fri +1w>\n:LOGBOOK:\n- Rescheduled from \"[2023-09-05 Tue +1w]\" on [2023-09-06 Wed 18:30]\n:END:\n"
+        );
+    }
+}
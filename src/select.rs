@@ -0,0 +1,144 @@
+//! Selection of a single heading from the command line.
+//! A selector is either "FILE:LINE", the file being matched by the end of its path, or a name:
+//! the ":ID:" property of a heading, else its outline path. Example: "Work / Deploy"
+use crate::ical::ID_PROPERTY;
+use crate::parsing::document::{Document, Heading};
+use std::convert::Infallible;
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// A way to find a heading.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Selector {
+    /// Heading at a line of a file, starting at 1
+    Line(PathBuf, usize),
+    /// Heading with this ID, or else with this outline path
+    Name(String),
+}
+
+/// Error returned when a selector does not match exactly one heading.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SelectError {
+    NotFound(String),
+    /// The selector matches the headings at these "FILE:LINE" locations
+    Ambiguous(String, Vec<String>),
+}
+
+impl Selector {
+    /// Find the heading matching the selector in `documents`.
+    pub fn find<'a, I>(&self, documents: I) -> Result<(&'a Document, &'a Heading), SelectError>
+    where
+        I: IntoIterator<Item = &'a Document>,
+    {
+        let documents: Vec<&Document> = documents.into_iter().collect();
+        let mut found: Vec<(&Document, &Heading)> = vec![];
+        match self {
+            Selector::Line(path, line) => {
+                for doc in documents.iter().filter(|d| d.path.ends_with(path)) {
+                    found.extend(doc.iter().filter(|h| h.line == *line).map(|h| (*doc, h)));
+                }
+            }
+            Selector::Name(name) => {
+                for doc in &documents {
+                    let ids = doc.iter().filter(|h| h.property(ID_PROPERTY) == Some(name));
+                    found.extend(ids.map(|h| (*doc, h)));
+                }
+                if found.is_empty() {
+                    for doc in &documents {
+                        doc.visit(|heading, inherited| {
+                            let mut outline = inherited.outline.clone();
+                            outline.push(heading.title.clone());
+                            if outline.join(" / ") == *name {
+                                found.push((doc, heading));
+                            }
+                        });
+                    }
+                }
+            }
+        }
+        match found.as_slice() {
+            [] => Err(SelectError::NotFound(self.to_string())),
+            [one] => Ok(*one),
+            many => Err(SelectError::Ambiguous(
+                self.to_string(),
+                many.iter()
+                    .map(|(doc, h)| format!("{}:{}", doc.path.display(), h.line))
+                    .collect(),
+            )),
+        }
+    }
+}
+
+impl FromStr for Selector {
+    type Err = Infallible;
+    /// Parse "FILE:LINE" when the text ends with ":" and a number, else a name
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let location = s
+            .rsplit_once(':')
+            .and_then(|(path, line)| Some((path, line.parse().ok()?)))
+            .filter(|(path, _)| !path.is_empty());
+        Ok(match location {
+            Some((path, line)) => Selector::Line(PathBuf::from(path), line),
+            None => Selector::Name(String::from(s)),
+        })
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Selector::Line(path, line) => write!(f, "{}:{line}", path.display()),
+            Selector::Name(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+impl fmt::Display for SelectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SelectError::NotFound(selector) => write!(f, "no heading matches {selector}"),
+            SelectError::Ambiguous(selector, locations) => write!(
+                f,
+                "{selector} matches several headings: {}",
+                locations.join(", ")
+            ),
+        }
+    }
+}
+
+impl Error for SelectError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{SelectError, Selector};
+    use crate::parsing::document::Document;
+    use std::path::Path;
+
+    #[test]
+    fn select_headings() {
+        let docs: Vec<Document> = [
+            (
+                "/org/work.org",
+                "* Work\n** TODO Deploy\n:PROPERTIES:\n:ID: deploy\n:END:\n",
+            ),
+            ("/org/home.org", "* Work\n** TODO Deploy\n"),
+        ]
+        .into_iter()
+        .map(|(path, source)| {
+            Document::parse(Path::new(path), source.into(), &Default::default()).value
+        })
+        .collect();
+        let find = |text: &str| {
+            let selector: Selector = text.parse().unwrap();
+            selector
+                .find(&docs)
+                .map(|(doc, h)| format!("{}:{}", doc.path.display(), h.line))
+        };
+        assert_eq!(find("deploy").as_deref(), Ok("/org/work.org:2"));
+        assert_eq!(find("home.org:2").as_deref(), Ok("/org/home.org:2"));
+        assert!(matches!(find("Work / Deploy"), Err(SelectError::Ambiguous(_, l)) if l.len() == 2));
+        assert!(matches!(find("work.org:3"), Err(SelectError::NotFound(_))));
+    }
+}
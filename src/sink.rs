@@ -0,0 +1,281 @@
+//! Notification sinks, i.e. the ways a reminder is delivered to the user.
+//! - `StdoutSink` prints the reminder, which ends up in journald when run as a service;
+//! - `CommandSink` runs an arbitrary command, such as `notify-send` or a `say` script;
+//! - `DesktopSink` sends a freedesktop notification over D-Bus (with the "desktop" feature).
+//!
+//! Sinks are selected by a list of `SinkConfig` and combined into a single `MultiSink`.
+use crate::reminder::Reminder;
+use std::error::Error;
+use std::fmt;
+use std::io::Write;
+use std::process::Command;
+use std::str::FromStr;
+
+/// Result of the delivery of a notification.
+pub type SinkResult = Result<(), Box<dyn Error + Send + Sync>>;
+
+/// A way to deliver reminders.
+pub trait NotificationSink: Send {
+    fn notify(&mut self, reminder: &Reminder) -> SinkResult;
+}
+
+/// Print reminders on the standard output, one per line.
+#[derive(Debug, Default)]
+pub struct StdoutSink;
+
+/// Run a command for every reminder.
+/// The placeholders "{title}", "{time}" and "{minutes}" of the arguments are replaced by the
+/// title of the todo, the time of the event and the number of minutes before the event.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CommandSink {
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+/// Send freedesktop notifications over D-Bus.
+#[cfg(feature = "desktop")]
+#[derive(Debug, Default)]
+pub struct DesktopSink;
+
+/// Deliver every reminder to several sinks.
+/// A failing sink does not prevent the others from being notified.
+#[derive(Default)]
+pub struct MultiSink {
+    sinks: Vec<Box<dyn NotificationSink>>,
+}
+
+/// Configuration of a single sink.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SinkConfig {
+    Stdout,
+    Desktop,
+    Command(CommandSink),
+}
+
+/// Error returned when a sink configuration cannot be used.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SinkConfigError {
+    /// The name of the sink is not known
+    Unknown(String),
+    /// The command sink has no program
+    EmptyCommand,
+    /// The binary was built without the "desktop" feature
+    DesktopUnsupported,
+}
+
+impl NotificationSink for StdoutSink {
+    fn notify(&mut self, reminder: &Reminder) -> SinkResult {
+        writeln!(std::io::stdout(), "{reminder}")?;
+        Ok(())
+    }
+}
+
+impl CommandSink {
+    /// Arguments of the command for a given reminder, with their placeholders replaced.
+    pub fn args_for(&self, reminder: &Reminder) -> Vec<String> {
+        let title = reminder.todo.title();
+        let time = reminder.event.format("%H:%M").to_string();
+        let minutes = reminder.lead.num_minutes().to_string();
+        self.args
+            .iter()
+            .map(|arg| {
+                arg.replace("{title}", title)
+                    .replace("{time}", &time)
+                    .replace("{minutes}", &minutes)
+            })
+            .collect()
+    }
+}
+
+impl NotificationSink for CommandSink {
+    fn notify(&mut self, reminder: &Reminder) -> SinkResult {
+        let mut child = Command::new(&self.program)
+            .args(self.args_for(reminder))
+            .spawn()?;
+        // The command may take a while (e.g. text to speech), the daemon must not wait for it
+        std::thread::spawn(move || child.wait());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "desktop")]
+impl NotificationSink for DesktopSink {
+    fn notify(&mut self, reminder: &Reminder) -> SinkResult {
+        let minutes = reminder.lead.num_minutes();
+        let time = reminder.event.format("%H:%M");
+        notify_rust::Notification::new()
+            .appname("orgparser")
+            .summary(reminder.todo.title())
+            .body(&format!("In {minutes} min, at {time}"))
+            .show()?;
+        Ok(())
+    }
+}
+
+impl MultiSink {
+    /// Build the sinks of a configuration.
+    pub fn from_configs(configs: &[SinkConfig]) -> Result<MultiSink, SinkConfigError> {
+        let mut multi = MultiSink::default();
+        for config in configs {
+            multi.push(config.build()?);
+        }
+        Ok(multi)
+    }
+    pub fn push(&mut self, sink: Box<dyn NotificationSink>) {
+        self.sinks.push(sink)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+}
+
+impl NotificationSink for MultiSink {
+    /// Notify every sink, and return the first error, if any.
+    fn notify(&mut self, reminder: &Reminder) -> SinkResult {
+        let mut result = Ok(());
+        for sink in self.sinks.iter_mut() {
+            let notified = sink.notify(reminder);
+            if result.is_ok() {
+                result = notified;
+            }
+        }
+        result
+    }
+}
+
+impl SinkConfig {
+    /// Build the sink described by the configuration.
+    pub fn build(&self) -> Result<Box<dyn NotificationSink>, SinkConfigError> {
+        match self {
+            SinkConfig::Stdout => Ok(Box::new(StdoutSink)),
+            SinkConfig::Command(command) if command.program.is_empty() => {
+                Err(SinkConfigError::EmptyCommand)
+            }
+            SinkConfig::Command(command) => Ok(Box::new(command.clone())),
+            #[cfg(feature = "desktop")]
+            SinkConfig::Desktop => Ok(Box::new(DesktopSink)),
+            #[cfg(not(feature = "desktop"))]
+            SinkConfig::Desktop => Err(SinkConfigError::DesktopUnsupported),
+        }
+    }
+}
+
+impl FromStr for SinkConfig {
+    type Err = SinkConfigError;
+    /// Parse a sink as "stdout", "desktop" or "command:PROGRAM ARGS...".
+    /// Arguments of a command are separated by blank spaces.
+    /// Example: "command:notify-send orgparser {title}"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "stdout" => Ok(SinkConfig::Stdout),
+            "desktop" => Ok(SinkConfig::Desktop),
+            other => {
+                let command = other
+                    .strip_prefix("command:")
+                    .ok_or_else(|| SinkConfigError::Unknown(String::from(other)))?;
+                let mut words = command.split_whitespace().map(String::from);
+                let program = words.next().ok_or(SinkConfigError::EmptyCommand)?;
+                Ok(SinkConfig::Command(CommandSink {
+                    program,
+                    args: words.collect(),
+                }))
+            }
+        }
+    }
+}
+
+impl fmt::Display for SinkConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SinkConfigError::Unknown(name) => write!(f, "unknown notification sink: {name}"),
+            SinkConfigError::EmptyCommand => write!(f, "the command sink needs a program"),
+            SinkConfigError::DesktopUnsupported => {
+                write!(f, "desktop notifications need the \"desktop\" feature")
+            }
+        }
+    }
+}
+
+impl Error for SinkConfigError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing::Todo;
+    use chrono::{Duration, NaiveDate};
+    use std::sync::{Arc, Mutex};
+
+    /// Fake sink recording the reminders it receives.
+    struct FakeSink {
+        received: Arc<Mutex<Vec<String>>>,
+        fail: bool,
+    }
+
+    impl NotificationSink for FakeSink {
+        fn notify(&mut self, reminder: &Reminder) -> SinkResult {
+            self.received.lock().unwrap().push(reminder.to_string());
+            if self.fail {
+                return Err("fake failure".into());
+            }
+            Ok(())
+        }
+    }
+
+    fn reminder() -> Reminder {
+        let todo =
+            Todo::parse_entry("* TODO Standup", Some("SCHEDULED: <2023-09-05 Tue 09:00>")).unwrap();
+        Reminder {
+            event: NaiveDate::from_ymd_opt(2023, 9, 5)
+                .unwrap()
+                .and_hms_opt(9, 0, 0)
+                .unwrap(),
+            todo,
+            lead: Duration::minutes(5),
+        }
+    }
+
+    #[test]
+    fn every_sink_is_notified() {
+        let received = Arc::new(Mutex::new(vec![]));
+        let mut multi = MultiSink::default();
+        for fail in [true, false] {
+            multi.push(Box::new(FakeSink {
+                received: received.clone(),
+                fail,
+            }));
+        }
+        assert!(multi.notify(&reminder()).is_err());
+        assert_eq!(received.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn command_placeholders() {
+        let config: SinkConfig = "command:notify-send -a orgparser {title} {minutes}min@{time}"
+            .parse()
+            .unwrap();
+        let SinkConfig::Command(command) = config else {
+            panic!("expected a command sink")
+        };
+        assert_eq!(command.program, "notify-send");
+        assert_eq!(
+            command.args_for(&reminder()),
+            ["-a", "orgparser", "Standup", "5min@09:00"]
+        );
+    }
+
+    #[test]
+    fn sink_configs() {
+        assert_eq!("stdout".parse(), Ok(SinkConfig::Stdout));
+        assert_eq!("desktop".parse(), Ok(SinkConfig::Desktop));
+        assert_eq!(
+            "email".parse::<SinkConfig>(),
+            Err(SinkConfigError::Unknown(String::from("email")))
+        );
+        assert_eq!(
+            "command:".parse::<SinkConfig>(),
+            Err(SinkConfigError::EmptyCommand)
+        );
+        let multi = MultiSink::from_configs(&[SinkConfig::Stdout]).unwrap();
+        assert!(!multi.is_empty());
+    }
+}
@@ -0,0 +1,339 @@
+//! Watch the org directories and re-index the files that change.
+//! Only the changed files are parsed again. Editors save files in several steps (write a
+//! temporary file, rename it, remove the ".#file.org" lock), so events are gathered for a short
+//! while before the index is updated, and paths that are not org files are ignored.
+use crate::cache::{Cache, CachedFile};
+use crate::parsing::document::Document;
+use crate::parsing::error::{ParseError, Parsed};
+use crate::parsing::file::WriteError;
+use crate::parsing::keywords::TodoKeywords;
+use crate::parsing::{get_org_entries, is_org_path, TodoVec};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc::{self, Sender};
+use tokio::time::sleep;
+
+/// Time during which events are gathered before the index is updated.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// The todos and documents of every org file under a set of root directories, by file.
+/// Files under an excluded path are not indexed.
+#[derive(Debug)]
+pub struct TodoIndex {
+    roots: Vec<PathBuf>,
+    exclude: Vec<PathBuf>,
+    keywords: TodoKeywords,
+    todos: HashMap<PathBuf, TodoVec>,
+    /// Documents of the files that were parsed, i.e. not taken from the cache
+    files: HashMap<PathBuf, Document>,
+    /// Errors of the last parsing of each file
+    file_errors: HashMap<PathBuf, Vec<ParseError>>,
+    /// Errors of the last walk of the roots
+    walk_errors: Vec<ParseError>,
+    /// Todos of the files that did not change since the last run
+    cache: Cache,
+}
+
+impl TodoIndex {
+    /// Parse every org file under `roots`, except the ones under `exclude`.
+    pub async fn build(
+        roots: Vec<PathBuf>,
+        exclude: Vec<PathBuf>,
+        keywords: TodoKeywords,
+    ) -> TodoIndex {
+        TodoIndex::build_with_cache(roots, exclude, keywords, Cache::disabled()).await
+    }
+    /// Like `build`, but the todos of the files that did not change are taken from `cache`,
+    /// and these files have no document.
+    pub async fn build_with_cache(
+        roots: Vec<PathBuf>,
+        exclude: Vec<PathBuf>,
+        keywords: TodoKeywords,
+        cache: Cache,
+    ) -> TodoIndex {
+        let mut index = TodoIndex {
+            roots,
+            exclude,
+            keywords,
+            todos: HashMap::new(),
+            files: HashMap::new(),
+            file_errors: HashMap::new(),
+            walk_errors: vec![],
+            cache,
+        };
+        index.rescan().await;
+        index
+    }
+    /// Save the cache with the files currently indexed.
+    pub fn save_cache(&mut self) -> Result<(), WriteError> {
+        let files = self
+            .todos
+            .iter()
+            .map(|(path, todos)| (path.as_path(), todos));
+        self.cache.save(files, &self.keywords)
+    }
+    /// Parse every org file again, e.g. after events were lost.
+    pub async fn rescan(&mut self) {
+        self.todos.clear();
+        self.files.clear();
+        self.file_errors.clear();
+        self.walk_errors.clear();
+        for root in self.roots.clone() {
+            let entries = get_org_entries(&root);
+            self.walk_errors.extend(entries.errors);
+            for file in entries.value {
+                if !self.is_ignored(&file) {
+                    self.update_file(&file).await;
+                }
+            }
+        }
+    }
+    /// Update the index after a change of `path`.
+    /// Returns true if the todos changed.
+    pub async fn update(&mut self, path: &Path) -> bool {
+        if self.is_ignored(path) {
+            return false;
+        }
+        if path.is_dir() {
+            // A directory was created or moved inside a root
+            let mut changed = false;
+            for file in get_org_entries(path).value {
+                if !self.is_ignored(&file) {
+                    changed |= self.update_file(&file).await;
+                }
+            }
+            changed
+        } else if is_org_path(path) {
+            self.update_file(path).await
+        } else if !path.exists() {
+            // A directory was removed or moved outside of the roots
+            let before = self.todos.len();
+            self.todos.retain(|file, _| !file.starts_with(path));
+            self.files.retain(|file, _| !file.starts_with(path));
+            self.file_errors.retain(|file, _| !file.starts_with(path));
+            before != self.todos.len()
+        } else {
+            false
+        }
+    }
+    /// Parse a single file again. A file that cannot be read anymore is removed, and its
+    /// error is kept unless the file was deleted.
+    async fn update_file(&mut self, path: &Path) -> bool {
+        let (file, errors) = match self.cache.read(path, &self.keywords).await {
+            Ok(Parsed { value, errors }) => (Some(value), errors),
+            Err(_) if !path.exists() => (None, vec![]),
+            Err(e) => (None, vec![e]),
+        };
+        if errors.is_empty() {
+            self.file_errors.remove(path);
+        } else {
+            self.file_errors.insert(path.to_path_buf(), errors);
+        }
+        match file {
+            Some(CachedFile { document, todos }) => {
+                match document {
+                    Some(document) => self.files.insert(path.to_path_buf(), document),
+                    None => self.files.remove(path),
+                };
+                let old = self.todos.insert(path.to_path_buf(), todos);
+                old.as_ref() != self.todos.get(path)
+            }
+            None => {
+                self.files.remove(path);
+                self.todos.remove(path).is_some()
+            }
+        }
+    }
+    /// Verify if a path is excluded, is inside a hidden directory or is a hidden file,
+    /// relatively to its root.
+    fn is_ignored(&self, path: &Path) -> bool {
+        if self
+            .exclude
+            .iter()
+            .any(|excluded| path.starts_with(excluded))
+        {
+            return true;
+        }
+        self.roots
+            .iter()
+            .find_map(|root| path.strip_prefix(root).ok())
+            .map(|relative| {
+                relative
+                    .components()
+                    .any(|c| c.as_os_str().to_str().is_some_and(|s| s.starts_with('.')))
+            })
+            .unwrap_or(true)
+    }
+    /// The todos of every indexed file.
+    pub fn todos(&self) -> TodoVec {
+        self.todos.values().flatten().cloned().collect()
+    }
+    /// The todos of every indexed file, without copying them.
+    pub fn into_todos(self) -> TodoVec {
+        self.todos.into_values().flatten().collect()
+    }
+    /// The documents of every indexed file that was parsed: the files taken from the cache
+    /// have none.
+    pub fn documents(&self) -> impl Iterator<Item = &Document> {
+        self.files.values()
+    }
+    /// Errors met while walking the roots and parsing the files.
+    pub fn errors(&self) -> impl Iterator<Item = &ParseError> {
+        self.walk_errors
+            .iter()
+            .chain(self.file_errors.values().flatten())
+    }
+    /// Number of indexed files.
+    pub fn len(&self) -> usize {
+        self.todos.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.todos.is_empty()
+    }
+}
+
+/// Watch the roots of `index` and send the todos on `updates` every time they change.
+/// Returns when `updates` is closed, or with an error if the roots cannot be watched.
+pub async fn watch(mut index: TodoIndex, updates: Sender<TodoVec>) -> notify::Result<()> {
+    let (sender, mut events) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event| {
+        let _ = sender.send(event);
+    })?;
+    for root in &index.roots {
+        watcher.watch(root, RecursiveMode::Recursive)?;
+    }
+    while let Some(event) = events.recv().await {
+        let mut paths = HashSet::new();
+        let mut rescan = collect_paths(event, &mut paths);
+        let debounce = sleep(DEBOUNCE);
+        tokio::pin!(debounce);
+        loop {
+            tokio::select! {
+                _ = &mut debounce => break,
+                Some(event) = events.recv() => rescan |= collect_paths(event, &mut paths),
+            }
+        }
+        let changed = if rescan {
+            index.rescan().await;
+            true
+        } else {
+            let mut changed = false;
+            for path in paths {
+                changed |= index.update(&path).await;
+            }
+            changed
+        };
+        if changed && updates.send(index.todos()).await.is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Add the paths of an event to `paths`.
+/// Returns true if events were lost and every file must be parsed again.
+fn collect_paths(event: notify::Result<Event>, paths: &mut HashSet<PathBuf>) -> bool {
+    match event {
+        Ok(event) if event.need_rescan() => true,
+        Ok(Event {
+            kind: EventKind::Access(_),
+            ..
+        }) => false,
+        Ok(event) => {
+            paths.extend(event.paths);
+            false
+        }
+        Err(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{watch, TodoIndex};
+    use std::fs;
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+    use tokio::time::timeout;
+
+    fn org_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("orgparser-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::create_dir_all(dir.join(".git")).unwrap();
+        fs::write(
+            dir.join("a.org"),
+            "* TODO A\nSCHEDULED: <2030-01-01 Tue 10:00>\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("sub/b.org"),
+            "* TODO B\nSCHEDULED: <2030-01-02 Wed 10:00>\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join(".git/c.org"),
+            "* TODO C\nSCHEDULED: <2030-01-02 Wed 10:00>\n",
+        )
+        .unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn index_updates() {
+        let dir = org_dir("index");
+        let mut index = TodoIndex::build(vec![dir.clone()], vec![], Default::default()).await;
+        assert_eq!(index.len(), 2);
+        assert_eq!(index.todos().len(), 2);
+
+        let excluded =
+            TodoIndex::build(vec![dir.clone()], vec![dir.join("sub")], Default::default()).await;
+        assert_eq!(excluded.len(), 1);
+
+        // Emacs lock files and hidden directories are ignored
+        assert!(!index.update(&dir.join(".#a.org")).await);
+        assert!(!index.update(&dir.join(".git/c.org")).await);
+        // Saving without change does not change the todos
+        assert!(!index.update(&dir.join("a.org")).await);
+
+        // Temp file renamed onto the org file
+        fs::write(
+            dir.join("a.org.tmp"),
+            "* TODO A2\nSCHEDULED: <2030-01-01 Tue 11:00>\n",
+        )
+        .unwrap();
+        fs::rename(dir.join("a.org.tmp"), dir.join("a.org")).unwrap();
+        assert!(!index.update(&dir.join("a.org.tmp")).await);
+        assert!(index.update(&dir.join("a.org")).await);
+        assert!(index.todos().iter().any(|t| t.title() == "A2"));
+
+        // Removed directory
+        fs::remove_dir_all(dir.join("sub")).unwrap();
+        assert!(index.update(&dir.join("sub")).await);
+        assert_eq!(index.len(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn watcher_sends_updates() {
+        let dir = org_dir("watch");
+        let index = TodoIndex::build(vec![dir.clone()], vec![], Default::default()).await;
+        let (sender, mut receiver) = mpsc::channel(1);
+        tokio::spawn(watch(index, sender));
+        // Give the watcher some time to start
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        fs::write(
+            dir.join("new.org"),
+            "* TODO New\nSCHEDULED: <2030-01-03 Thu 10:00>\n",
+        )
+        .unwrap();
+        let todos = timeout(Duration::from_secs(5), receiver.recv())
+            .await
+            .expect("no update from the watcher")
+            .unwrap();
+        assert_eq!(todos.len(), 3);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}